@@ -5,12 +5,209 @@
 // https://stackoverflow.com/questions/4842424/list-of-ansi-color-escape-sequences
 // https://gist.github.com/fnky/458719343aabd01cfb17a3a4f7296797
 
-use std::fmt::Display;
+/// A color of the 256 color palette or a 24-bit rgb color.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ColorValue {
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ColorError {
+    /// A palette index above 255.
+    OutOfRange(String),
+    /// Neither a palette index nor a hex string with 6 digits.
+    Malformed(String),
+}
 
+/// The colors a terminal is able to display.
 #[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorMode {
+    TrueColor,
+    Indexed256,
+    Indexed16,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct Color {
-    pub bg_color: Option<u8>,
-    pub fg_color: Option<u8>,
+    pub bg_color: Option<ColorValue>,
+    pub fg_color: Option<ColorValue>,
+}
+
+/// The usual xterm values of the 16 standard colors.
+const STANDARD_COLORS: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// The channel values of the 6x6x6 color cube of the 256 color palette.
+const CUBE_VALUES: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl ColorValue {
+    /// Reads a palette index like `214` or a hex string like `"#ffaf00"`.
+    /// Anything else, e.g. `nan`, means no color.
+    pub fn parse(value: &toml::Value) -> Result<Option<Self>, ColorError> {
+        if let Some(index) = value.as_integer() {
+            return u8::try_from(index)
+                .map(|i| Some(ColorValue::Indexed(i)))
+                .map_err(|_| ColorError::OutOfRange(index.to_string()));
+        }
+
+        match value.as_str() {
+            Some(s) => Self::parse_str(s).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads a palette index like `"214"` or a hex string like `"#ffaf00"`.
+    pub fn parse_str(s: &str) -> Result<Self, ColorError> {
+        let Some(hex) = s.strip_prefix('#') else {
+            return match s.parse::<u8>() {
+                Ok(index) => Ok(ColorValue::Indexed(index)),
+                Err(_) if s.parse::<u64>().is_ok() => Err(ColorError::OutOfRange(s.to_string())),
+                Err(_) => Err(ColorError::Malformed(s.to_string())),
+            };
+        };
+
+        if hex.len() != 6
+            || !hex
+                .chars()
+                .all(|c| c.is_ascii_hexdigit())
+        {
+            return Err(ColorError::Malformed(s.to_string()));
+        }
+
+        let rgb = u32::from_str_radix(hex, 16).map_err(|_| ColorError::Malformed(s.to_string()))?;
+
+        Ok(ColorValue::Rgb(
+            (rgb >> 16) as u8,
+            (rgb >> 8) as u8,
+            rgb as u8,
+        ))
+    }
+
+    pub fn to_rgb(self) -> (u8, u8, u8) {
+        match self {
+            ColorValue::Rgb(r, g, b) => (r, g, b),
+            ColorValue::Indexed(i) if i < 16 => STANDARD_COLORS[i as usize],
+            ColorValue::Indexed(i) if i < 232 => {
+                let i = i - 16;
+                (
+                    CUBE_VALUES[(i / 36) as usize],
+                    CUBE_VALUES[(i / 6 % 6) as usize],
+                    CUBE_VALUES[(i % 6) as usize],
+                )
+            }
+            ColorValue::Indexed(i) => {
+                let gray = 8 + (i - 232) * 10;
+                (gray, gray, gray)
+            }
+        }
+    }
+
+    /// The color with its channels scaled by `brightness`, between 0 and 1.
+    pub fn darken(self, brightness: f32) -> Self {
+        let (r, g, b) = self.to_rgb();
+        let scale = |c: u8| (c as f32 * brightness).round() as u8;
+
+        ColorValue::Rgb(scale(r), scale(g), scale(b))
+    }
+
+    /// The color moved towards `other` by `amount`, between 0 and 1.
+    pub fn blend(self, other: ColorValue, amount: f32) -> Self {
+        let (r, g, b) = self.to_rgb();
+        let (or, og, ob) = other.to_rgb();
+        let mix = |c: u8, o: u8| (c as f32 + (o as f32 - c as f32) * amount).round() as u8;
+
+        ColorValue::Rgb(mix(r, or), mix(g, og), mix(b, ob))
+    }
+
+    /// The closest color `mode` is able to display.
+    pub fn downsample(self, mode: ColorMode) -> Self {
+        match (mode, self) {
+            (ColorMode::TrueColor, _) => self,
+            (ColorMode::Indexed256, ColorValue::Indexed(_)) => self,
+            (ColorMode::Indexed256, ColorValue::Rgb(r, g, b)) => {
+                ColorValue::Indexed(closest_index(r, g, b, 16..=255))
+            }
+            (ColorMode::Indexed16, ColorValue::Indexed(i)) if i < 16 => self,
+            (ColorMode::Indexed16, _) => {
+                let (r, g, b) = self.to_rgb();
+                ColorValue::Indexed(closest_index(r, g, b, 0..=15))
+            }
+        }
+    }
+
+    /// The SGR parameters selecting this color, after downsampling to `mode`.
+    fn sgr(self, mode: ColorMode, background: bool) -> String {
+        match self.downsample(mode) {
+            ColorValue::Rgb(r, g, b) => {
+                let base = if background { 48 } else { 38 };
+                format!("{base};2;{r};{g};{b}")
+            }
+            ColorValue::Indexed(i) if mode == ColorMode::Indexed16 => {
+                let base = if background { 40 } else { 30 };
+                if i < 8 {
+                    format!("{}", base + i)
+                } else {
+                    format!("{}", base + 60 + i - 8)
+                }
+            }
+            ColorValue::Indexed(i) => {
+                let base = if background { 48 } else { 38 };
+                format!("{base};5;{i}")
+            }
+        }
+    }
+}
+
+/// The palette index in `indices` closest to the rgb color.
+fn closest_index(r: u8, g: u8, b: u8, indices: std::ops::RangeInclusive<u8>) -> u8 {
+    let distance = |i: &u8| {
+        let (ir, ig, ib) = ColorValue::Indexed(*i).to_rgb();
+        let dr = r as i32 - ir as i32;
+        let dg = g as i32 - ig as i32;
+        let db = b as i32 - ib as i32;
+        dr * dr + dg * dg + db * db
+    };
+
+    indices.min_by_key(distance).unwrap()
+}
+
+impl ColorMode {
+    /// Guesses the color mode of the terminal from the environment.
+    pub fn detect() -> Self {
+        let colorterm = std::env::var("COLORTERM").ok();
+        let term = std::env::var("TERM").ok();
+
+        Self::from_env(colorterm.as_deref(), term.as_deref())
+    }
+
+    fn from_env(colorterm: Option<&str>, term: Option<&str>) -> Self {
+        if matches!(colorterm, Some("truecolor") | Some("24bit")) {
+            return ColorMode::TrueColor;
+        }
+
+        match term {
+            Some(term) if term.contains("direct") => ColorMode::TrueColor,
+            Some(term) if term.contains("256color") => ColorMode::Indexed256,
+            _ => ColorMode::Indexed16,
+        }
+    }
 }
 
 impl Color {
@@ -18,8 +215,8 @@ impl Color {
 
     pub const fn new(bg_color: u8, fg_color: u8) -> Self {
         Self {
-            bg_color: Some(bg_color),
-            fg_color: Some(fg_color),
+            bg_color: Some(ColorValue::Indexed(bg_color)),
+            fg_color: Some(ColorValue::Indexed(fg_color)),
         }
     }
 
@@ -31,38 +228,96 @@ impl Color {
     }
 
     pub const fn null() -> Self {
-        Self {
-            bg_color: Some(0),
-            fg_color: Some(0),
-        }
+        Self::new(0, 0)
     }
 
     pub const fn text() -> Self {
-        Self {
-            bg_color: Some(0),
-            fg_color: Some(7),
-        }
+        Self::new(0, 7)
     }
-}
 
-impl From<&Color> for String {
-    fn from(color: &Color) -> Self {
-        if let Some(bg_color) = color.bg_color {
-            if let Some(fg_color) = color.fg_color {
-                return format!("\x1b[38;5;{fg_color};48;5;{bg_color}m");
-            } else {
-                return format!("\x1b[48;5;{bg_color}m");
-            }
-        } else if let Some(fg_color) = color.fg_color {
-            return format!("\x1b[38;5;{fg_color}m");
+    /// The escape sequence setting the present colors in a terminal with `mode`.
+    pub fn escape(&self, mode: ColorMode) -> String {
+        let parameters: Vec<String> = [
+            self.fg_color
+                .map(|c| c.sgr(mode, false)),
+            self.bg_color.map(|c| c.sgr(mode, true)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        if parameters.is_empty() {
+            return String::new();
         }
 
-        String::new()
+        format!("\x1b[{}m", parameters.join(";"))
     }
 }
 
-impl Display for Color {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", String::from(self))
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_value_parse() {
+        assert_eq!(
+            ColorValue::parse(&toml::Value::Integer(214)),
+            Ok(Some(ColorValue::Indexed(214)))
+        );
+        assert_eq!(
+            ColorValue::parse(&toml::Value::String(String::from("#ffaf00"))),
+            Ok(Some(ColorValue::Rgb(255, 175, 0)))
+        );
+        assert_eq!(ColorValue::parse(&toml::Value::Float(f64::NAN)), Ok(None));
+        assert_eq!(
+            ColorValue::parse(&toml::Value::Integer(300)),
+            Err(ColorError::OutOfRange(String::from("300")))
+        );
+        assert_eq!(
+            ColorValue::parse_str("300"),
+            Err(ColorError::OutOfRange(String::from("300")))
+        );
+        assert_eq!(
+            ColorValue::parse_str("#fff"),
+            Err(ColorError::Malformed(String::from("#fff")))
+        );
+        assert_eq!(
+            ColorValue::parse_str("#+fffff"),
+            Err(ColorError::Malformed(String::from("#+fffff")))
+        );
+    }
+
+    #[test]
+    fn test_color_escape_downsampled() {
+        let color = Color {
+            bg_color: Some(ColorValue::Rgb(255, 175, 0)),
+            fg_color: Some(ColorValue::Indexed(196)),
+        };
+
+        assert_eq!(
+            color.escape(ColorMode::TrueColor),
+            "\x1b[38;5;196;48;2;255;175;0m"
+        );
+        assert_eq!(
+            color.escape(ColorMode::Indexed256),
+            "\x1b[38;5;196;48;5;214m"
+        );
+        assert_eq!(color.escape(ColorMode::Indexed16), "\x1b[91;43m");
+    }
+
+    #[test]
+    fn test_color_mode_from_env() {
+        assert_eq!(
+            ColorMode::from_env(Some("truecolor"), Some("xterm")),
+            ColorMode::TrueColor
+        );
+        assert_eq!(
+            ColorMode::from_env(None, Some("xterm-256color")),
+            ColorMode::Indexed256
+        );
+        assert_eq!(
+            ColorMode::from_env(None, Some("linux")),
+            ColorMode::Indexed16
+        );
     }
 }
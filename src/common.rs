@@ -5,8 +5,28 @@ use std::ops::Add;
 use std::ops::Sub;
 
 pub const TILE_SIZE: ScreenPoint = ScreenPoint::new(3, 1);
+pub const COMPACT_TILE_SIZE: ScreenPoint = ScreenPoint::new(1, 1);
 pub const FRAMES_PER_SECOND: u16 = 8;
 
+/// How many terminal cells a tile takes up.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Zoom {
+    /// Tiles are drawn with their animations, `TILE_SIZE` cells each.
+    Normal,
+    /// Tiles are drawn with their glyphs, a single cell each.
+    Compact,
+}
+
+impl Zoom {
+    /// The size of a tile on the screen in this zoom.
+    pub fn tile_size(self) -> ScreenPoint {
+        match self {
+            Zoom::Normal => TILE_SIZE,
+            Zoom::Compact => COMPACT_TILE_SIZE,
+        }
+    }
+}
+
 pub type MapPoint = Point<MapCoordinate>;
 pub type ScreenPoint = Point<ScreenCoordinate>;
 
@@ -57,6 +77,12 @@ impl<W> Point<W> {
     }
 }
 
+impl<W> Default for Point<W> {
+    fn default() -> Self {
+        Self::new(0, 0)
+    }
+}
+
 impl<W> std::fmt::Debug for Point<W> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{{ x: {}, y: {} }}", self.x, self.y)
@@ -95,19 +121,21 @@ impl<W> Sub for &Point<W> {
     }
 }
 
-impl From<ScreenPoint> for MapPoint {
-    fn from(p: ScreenPoint) -> Self {
-        MapPoint::new(p.x / TILE_SIZE.width(), p.y / TILE_SIZE.height())
+impl MapPoint {
+    /// The screen position of the top left cell of the tile at this position.
+    pub fn to_screen(&self, tile_size: &ScreenPoint) -> ScreenPoint {
+        ScreenPoint::new(self.x * tile_size.width(), self.y * tile_size.height())
     }
 }
 
-impl From<MapPoint> for ScreenPoint {
-    fn from(p: MapPoint) -> Self {
-        ScreenPoint::new(p.x * TILE_SIZE.width(), p.y * TILE_SIZE.height())
+impl ScreenPoint {
+    /// The map position of the tile covering the cell at this position.
+    pub fn to_map(&self, tile_size: &ScreenPoint) -> MapPoint {
+        MapPoint::new(self.x / tile_size.width(), self.y / tile_size.height())
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct RectAbsolute<W> {
     pub x1: W,
     pub y1: W,
@@ -115,6 +143,12 @@ pub struct RectAbsolute<W> {
     pub y2: W,
 }
 
+impl RectAbsolute<i32> {
+    pub fn size(&self) -> ScreenPoint {
+        ScreenPoint::new(self.x2 - self.x1, self.y2 - self.y1)
+    }
+}
+
 pub fn intersect(r1: &RectAbsolute<i32>, r2: &RectAbsolute<i32>) -> RectAbsolute<i32> {
     let x1 = max(r1.x1, r2.x1);
     let y1 = max(r1.y1, r2.y1);
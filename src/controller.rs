@@ -1,5 +1,4 @@
 use std::io::stdin;
-use std::rc::Rc;
 use std::sync::mpsc::sync_channel;
 use std::sync::mpsc::Receiver;
 use std::sync::mpsc::SyncSender;
@@ -12,11 +11,16 @@ use termion::input::TermRead;
 
 use crate::common::MapPoint;
 use crate::common::FRAMES_PER_SECOND;
+use crate::movement_profile::MovementProfile;
 use crate::renderer::Renderer;
-use crate::state::get_shortest_path;
+use crate::state::path::get_shortest_path;
+use crate::state::path::get_shortest_path_to_adjacent;
 use crate::state::task::goto::GotoTask;
 use crate::state::State;
 
+/// How many messages page up and page down scroll the message log.
+const MESSAGE_SCROLL_LINES: usize = 10;
+
 pub enum TerminalEvent {
     Key(Key),
     Resize,
@@ -106,35 +110,61 @@ impl Controller {
                 Key::Char('j') => self.state.move_map_up(),
 
                 Key::Char('d') => self.state.debug_info_next_page(),
+                Key::Char('f') => self.state.toggle_fog_of_war(),
+
+                Key::Char('z') => {
+                    self.renderer.toggle_zoom();
+                    self.resize();
+                }
+
+                Key::Char('o') => self.state.message_log.toggle(),
+                Key::PageUp => self
+                    .state
+                    .message_log
+                    .scroll_up(MESSAGE_SCROLL_LINES),
+                Key::PageDown => self
+                    .state
+                    .message_log
+                    .scroll_down(MESSAGE_SCROLL_LINES),
 
                 Key::Char(' ') => {
-                    let goto_task = GotoTask::new(
-                        MapPoint::new(
-                            self.state.cursor_pos.x - self.state.map_pos.x,
-                            self.state.cursor_pos.y - self.state.map_pos.y,
-                        ),
-                        Rc::clone(&self.state.map),
-                        Rc::clone(&self.state.tile_config),
-                    );
+                    let goto_task = GotoTask::new(MapPoint::new(
+                        self.state.cursor_pos.x - self.state.map_pos.x,
+                        self.state.cursor_pos.y - self.state.map_pos.y,
+                    ));
 
                     self.state
                         .cursor_tasks
                         .push(Box::new(goto_task));
                 }
 
+                Key::Char('r') => self.state.order_rally(MapPoint::new(
+                    self.state.cursor_pos.x - self.state.map_pos.x,
+                    self.state.cursor_pos.y - self.state.map_pos.y,
+                )),
+
                 Key::Char('\n') => self.state.toggle_selection(),
 
                 Key::Char('s') => self.state.set_astar_start(),
                 Key::Char('g') => self.state.set_astar_goal(),
                 Key::Char('*') => {
-                    let x = get_shortest_path(
+                    self.state.astar_path = get_shortest_path(
                         &self.state.astar_start,
                         &self.state.astar_goal,
                         &self.state.map,
                         &self.state.tile_config,
+                        &MovementProfile::default(),
                     );
-
-                    self.state.astar_path = x;
+                }
+                Key::Char('a') => {
+                    self.state.astar_path = get_shortest_path_to_adjacent(
+                        &self.state.astar_start,
+                        std::slice::from_ref(&self.state.astar_goal),
+                        &self.state.map,
+                        &self.state.tile_config,
+                        &MovementProfile::default(),
+                    )
+                    .map(|p| p.steps);
                 }
                 _ => {}
             },
@@ -152,6 +182,6 @@ impl Controller {
 
     fn resize(&mut self) {
         let size = self.renderer.resize();
-        self.state.resize(&size.into());
+        self.state.resize(&size);
     }
 }
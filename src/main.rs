@@ -3,10 +3,13 @@ use crate::controller::Controller;
 mod color;
 mod common;
 mod controller;
+mod movement_profile;
 mod npc_config;
 mod renderer;
 mod screen;
 mod state;
+mod style;
+mod terminal;
 mod tile_config;
 
 fn main() {
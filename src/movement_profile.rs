@@ -0,0 +1,139 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+use crate::tile_config::BaseTile;
+use crate::tile_config::TileConfig;
+use crate::tile_config::TileState;
+
+/// Describes how an npc moves across the map. Npcs without a `movement`
+/// table in the npc config use the default profile, which walks on solid
+/// floors through gas blocks.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct MovementProfile {
+    /// Whether the npc may step diagonally.
+    pub diagonal: bool,
+    /// Overrides `BaseTile::move_cost`, keyed by tile key.
+    pub move_costs: BTreeMap<String, u32>,
+    pub traversability: Traversability,
+}
+
+/// The part of a movement profile deciding which tiles can be entered at all.
+/// Connectivity of the map only depends on it, not on costs, so regions and
+/// chunk entrances are shared by profiles with the same traversability.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Traversability {
+    /// Block states the npc can move through.
+    pub block_states: Vec<TileState>,
+    /// Floor states the npc can move on.
+    pub floor_states: Vec<TileState>,
+    /// Tiles which are traversable regardless of their states, keyed by tile key.
+    pub traversable_tiles: BTreeSet<String>,
+}
+
+impl Traversability {
+    pub fn allows(&self, base_tile: &BaseTile) -> bool {
+        if self
+            .traversable_tiles
+            .contains(&base_tile.key)
+        {
+            return true;
+        }
+
+        self.block_states
+            .contains(&base_tile.block_state)
+            && self
+                .floor_states
+                .contains(&base_tile.floor_state)
+    }
+}
+
+impl MovementProfile {
+    pub fn move_cost(&self, base_tile: &BaseTile) -> u32 {
+        self.move_costs
+            .get(&base_tile.key)
+            .copied()
+            .unwrap_or(base_tile.move_cost)
+    }
+
+    /// The cheapest cost of entering any traversable tile, used to keep
+    /// pathfinding heuristics admissible.
+    pub fn min_move_cost(&self, tile_config: &TileConfig) -> u32 {
+        tile_config
+            .iter()
+            .filter(|t| t.is_traversable(self))
+            .map(|t| self.move_cost(t))
+            .min()
+            .unwrap_or(0)
+    }
+}
+
+impl Default for MovementProfile {
+    fn default() -> Self {
+        Self {
+            diagonal: false,
+            move_costs: BTreeMap::new(),
+            traversability: Traversability {
+                block_states: vec![TileState::Gas],
+                floor_states: vec![TileState::Solid],
+                traversable_tiles: BTreeSet::new(),
+            },
+        }
+    }
+}
+
+impl From<&toml::Value> for MovementProfile {
+    fn from(value: &toml::Value) -> Self {
+        let default = MovementProfile::default();
+
+        let tile_states = |key: &str| {
+            value
+                .get(key)
+                .and_then(|v| v.as_array())
+                .map(|a| {
+                    a.iter()
+                        .map(|s| TileState::from(s.as_str().unwrap()))
+                        .collect()
+                })
+        };
+
+        let block_states =
+            tile_states("block_states").unwrap_or(default.traversability.block_states);
+        let floor_states =
+            tile_states("floor_states").unwrap_or(default.traversability.floor_states);
+
+        let diagonal = value
+            .get("diagonal")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(default.diagonal);
+
+        let move_costs = value
+            .get("move_cost")
+            .and_then(|v| v.as_table())
+            .map(|t| {
+                t.iter()
+                    .map(|(key, cost)| (key.clone(), cost.as_integer().unwrap() as u32))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let traversable_tiles = value
+            .get("traversable_tiles")
+            .and_then(|v| v.as_array())
+            .map(|a| {
+                a.iter()
+                    .map(|s| s.as_str().unwrap().to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self {
+            diagonal,
+            move_costs,
+            traversability: Traversability {
+                block_states,
+                floor_states,
+                traversable_tiles,
+            },
+        }
+    }
+}
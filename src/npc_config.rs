@@ -3,7 +3,11 @@ use std::fs::read_to_string;
 use std::path::Path;
 
 use crate::color::Color;
+use crate::color::ColorValue;
+use crate::movement_profile::MovementProfile;
 use crate::screen::Animation;
+use crate::screen::Sprite;
+use crate::state::npc::Faction;
 use crate::state::npc::NpcClass;
 
 pub type NpcId = String;
@@ -11,10 +15,18 @@ pub type NpcId = String;
 #[derive(Debug)]
 pub struct BaseNpc {
     pub color: Color,
+    pub faction: Faction,
+    /// The npc drawn as a single cell in compact zoom.
+    pub glyph: Sprite,
     pub id: NpcId,
     pub key: String,
+    /// How many tiles far the torch of the npc shines, 0 without a torch.
+    pub light: i32,
+    pub movement_profile: MovementProfile,
     pub name: String,
     pub npc_class: NpcClass,
+    /// How many tiles far the npc sees.
+    pub vision: i32,
     pub walk_delay: i32,
     pub animation_idle: Vec<Animation>,
     pub animation_walk: Vec<Animation>,
@@ -30,6 +42,10 @@ impl NpcConfig {
         self.npcs.get(npc_id).unwrap()
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = &BaseNpc> {
+        self.npcs.values()
+    }
+
     pub fn from_file<P: AsRef<Path>>(path: P) -> Self {
         let mut npcs = HashMap::new();
 
@@ -41,12 +57,13 @@ impl NpcConfig {
         for (key, t) in tile_confg_table {
             let base = t["base"].as_table().unwrap();
 
-            let bg_color = base["bg_color"]
-                .as_integer()
-                .map(|c| c as u8);
-            let fg_color = base["fg_color"]
-                .as_integer()
-                .map(|c| c as u8);
+            let parse_color = |color_key: &str| {
+                ColorValue::parse(&base[color_key]).unwrap_or_else(|e| {
+                    panic!("Color '{color_key}' of npc '{key}' is invalid: {e:?}.")
+                })
+            };
+            let bg_color = parse_color("bg_color");
+            let fg_color = parse_color("fg_color");
             let color = Color { bg_color, fg_color };
 
             let id = base["id"]
@@ -59,12 +76,39 @@ impl NpcConfig {
                 .map(String::from)
                 .unwrap();
 
+            let glyph = match base
+                .get("glyph")
+                .and_then(|g| g.as_str())
+            {
+                Some(glyph) if glyph.chars().count() == 1 => glyph.to_string(),
+                Some(glyph) => panic!("Glyph '{glyph}' of npc '{key}' is not a single character."),
+                None => name.chars().take(1).collect(),
+            };
+            let glyph = Sprite::from_color_text(&glyph, color);
+
             let walk_delay = base["walk_delay"]
                 .as_integer()
                 .map(|i| i as i32)
                 .unwrap();
 
             let npc_class = NpcClass::from(base["npc_class"].as_str().unwrap());
+            let faction = Faction::from(base["faction"].as_str().unwrap());
+
+            let vision = base["vision"]
+                .as_integer()
+                .map(|i| i as i32)
+                .unwrap();
+
+            let light = base
+                .get("light")
+                .and_then(|l| l.as_integer())
+                .map(|l| l as i32)
+                .unwrap_or(0);
+
+            let movement_profile = t
+                .get("movement")
+                .map(MovementProfile::from)
+                .unwrap_or_default();
 
             let animation = t["animation"].as_table().unwrap();
 
@@ -81,16 +125,22 @@ impl NpcConfig {
                 .unwrap()
                 .iter()
                 .map(Animation::from)
+                .map(Animation::with_color(color))
                 .collect();
 
             npcs.insert(
                 id.clone(),
                 BaseNpc {
                     color,
+                    faction,
+                    glyph,
                     id,
                     key: key.to_string(),
+                    light,
+                    movement_profile,
                     name,
                     npc_class,
+                    vision,
                     walk_delay,
                     animation_idle,
                     animation_walk,
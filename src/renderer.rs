@@ -1,38 +1,107 @@
+use std::collections::HashMap;
+use std::io::stdout;
+use std::io::Stdout;
+
+use termion::raw::IntoRawMode;
+use termion::raw::RawTerminal;
+
+use self::layout::Layout;
+use self::shade::Shade;
 use crate::color::Color;
+use crate::color::ColorValue;
+use crate::common::intersect;
 use crate::common::MapPoint;
+use crate::common::RectAbsolute;
 use crate::common::ScreenPoint;
-use crate::screen::DefaultScreen;
+use crate::common::Zoom;
 use crate::screen::Pixel;
+use crate::screen::Screen;
 use crate::screen::Sprite;
 use crate::state::selection::Selection;
+use crate::state::visibility::Visibility;
 use crate::state::State;
+use crate::style::Style;
+use crate::terminal::Terminal;
 
 pub mod draw_debug_info;
+pub mod draw_minimap;
 pub mod draw_npcs;
+pub mod draw_panels;
+pub mod layout;
+pub mod shade;
 
-pub struct Renderer {
-    screen: DefaultScreen,
+pub struct Renderer<W: Terminal = RawTerminal<Stdout>> {
+    screen: Screen<W>,
+    layout: Layout,
+    /// How large tiles are drawn, the map viewport has to be resized on change.
+    pub zoom: Zoom,
+    shades: HashMap<(Color, Shade), Color>,
 
     debug_line_y: i32,
 }
 
 impl Renderer {
     pub fn new() -> Self {
-        let screen = DefaultScreen::new();
+        Self::with_terminal(stdout().into_raw_mode().unwrap())
+    }
+}
+
+impl<W: Terminal> Renderer<W> {
+    pub fn with_terminal(terminal: W) -> Self {
+        let screen = Screen::from(terminal);
+        let layout = Layout::new(&screen.size);
 
         Self {
             screen,
+            layout,
+            zoom: Zoom::Normal,
+            shades: HashMap::new(),
             debug_line_y: 0,
         }
     }
 
-    pub fn resize(&mut self) -> ScreenPoint {
-        self.screen.resize()
+    /// Adapts the layout to the terminal size and returns the size of the map
+    /// viewport in tiles.
+    pub fn resize(&mut self) -> MapPoint {
+        let size = self.screen.resize();
+        self.layout = Layout::new(&size);
+
+        self.layout
+            .map
+            .size()
+            .to_map(&self.zoom.tile_size())
+    }
+
+    pub fn toggle_zoom(&mut self) {
+        self.zoom = match self.zoom {
+            Zoom::Normal => Zoom::Compact,
+            Zoom::Compact => Zoom::Normal,
+        };
+    }
+
+    /// The screen position of the map position `point`.
+    fn to_screen(&self, point: &MapPoint) -> ScreenPoint {
+        point.to_screen(&self.zoom.tile_size())
+    }
+
+    /// Reduces text covering a tile to its middle character in compact zoom.
+    fn get_map_text(&self, text: &str) -> String {
+        match self.zoom {
+            Zoom::Normal => text.to_string(),
+            Zoom::Compact => text
+                .chars()
+                .nth(text.chars().count() / 2)
+                .map(String::from)
+                .unwrap_or_default(),
+        }
     }
 
     pub fn display(&mut self, state: &State) {
         self.screen.clear();
 
+        let layout = self.layout.clone();
+
+        self.screen.set_viewport(&layout.map);
         self.draw_floor(state);
         self.draw_map(state);
         self.draw_astar(state);
@@ -41,15 +110,33 @@ impl Renderer {
         self.draw_selection(state);
         self.draw_debug_info(state);
         self.draw_cursor(state);
+        self.draw_message_scrollback(state);
+
+        self.screen
+            .set_viewport(&layout.sidebar);
+        self.draw_sidebar(state);
 
+        self.screen
+            .set_viewport(&layout.minimap);
+        self.draw_minimap(state);
+
+        self.screen
+            .set_viewport(&layout.message_log);
+        self.draw_message_log(state);
+
+        self.screen
+            .set_viewport(&layout.status_bar);
+        self.draw_status_bar(state);
+
+        self.screen.reset_viewport();
         self.screen.display();
     }
 
     fn draw_dig_selection(&mut self, state: &State) {
         for dig_point in &state.dig_selection {
             self.screen.draw_color(
-                (dig_point + &state.map_pos).into(),
-                MapPoint::new(1, 1).into(),
+                self.to_screen(&(dig_point + &state.map_pos)),
+                self.to_screen(&MapPoint::new(1, 1)),
                 Color::new(32, 1),
             );
         }
@@ -69,37 +156,41 @@ impl Renderer {
             selection.normalize();
 
             self.screen.draw_inversion(
-                selection.pos.unwrap().into(),
-                (&selection.size.unwrap() + &MapPoint::new(1, 1)).into(),
+                self.to_screen(&selection.pos.unwrap()),
+                self.to_screen(&(&selection.size.unwrap() + &MapPoint::new(1, 1))),
             );
         }
     }
 
     fn draw_astar(&mut self, state: &State) {
         {
-            let astar_path_sprite = Sprite::from_color_text(" * ", Color::new(28, 0));
+            let astar_path_sprite =
+                Sprite::from_color_text(&self.get_map_text(" * "), Color::new(28, 0));
 
-            for step in &state.astar_path {
+            for step in state.astar_path.iter().flatten() {
+                let screen_point = self.to_screen(&(step + &state.map_pos));
                 self.screen
-                    .draw(&astar_path_sprite, (step + &state.map_pos).into());
+                    .draw(&astar_path_sprite, screen_point);
             }
         }
 
         {
-            let astar_start_sprite = Sprite::from_color_text(" S ", Color::new(34, 0));
+            let astar_start_sprite =
+                Sprite::from_color_text(&self.get_map_text(" S "), Color::new(34, 0));
 
             self.screen.draw(
                 &astar_start_sprite,
-                (&state.astar_start + &state.map_pos).into(),
+                self.to_screen(&(&state.astar_start + &state.map_pos)),
             );
         }
 
         {
-            let astar_goal_sprite = Sprite::from_color_text(" G ", Color::new(34, 0));
+            let astar_goal_sprite =
+                Sprite::from_color_text(&self.get_map_text(" G "), Color::new(34, 0));
 
             self.screen.draw(
                 &astar_goal_sprite,
-                (&state.astar_goal + &state.map_pos).into(),
+                self.to_screen(&(&state.astar_goal + &state.map_pos)),
             );
         }
     }
@@ -109,8 +200,9 @@ impl Renderer {
             ch: 'X',
             color: Color {
                 bg_color: None,
-                fg_color: Some(2),
+                fg_color: Some(ColorValue::Indexed(2)),
             },
+            style: Style::default(),
         }];
 
         let cursor = Sprite {
@@ -118,8 +210,11 @@ impl Renderer {
             size: ScreenPoint::new(1, 1),
         };
 
+        // centered in the tile
+        let offset = ScreenPoint::new(self.zoom.tile_size().width() / 2, 0);
+
         self.screen
-            .draw(&cursor, ScreenPoint::from(state.cursor_pos.clone()).right());
+            .draw(&cursor, &self.to_screen(&state.cursor_pos) + &offset);
     }
 
     fn draw_floor(&mut self, state: &State) {
@@ -128,24 +223,175 @@ impl Renderer {
         let height = state.screen_size.height();
 
         for _i in 0..(width * height) {
-            pixels.push(Pixel::from('['));
-            pixels.push(Pixel::from('-'));
-            pixels.push(Pixel::from(']'));
+            for ch in self.get_map_text("[-]").chars() {
+                pixels.push(Pixel::from(ch));
+            }
         }
 
         let sprite = Sprite {
             pixels,
-            size: state.screen_size.clone().into(),
+            size: self.to_screen(&state.screen_size),
         };
 
         self.screen
             .draw(&sprite, ScreenPoint::new(0, 0));
     }
 
+    /// Draws the tiles intersecting the screen, the rest of the map is skipped.
     fn draw_map(&mut self, state: &State) {
-        let sprite = state.get_map_sprite();
+        let tile_size = self.zoom.tile_size();
+        let screen_rect = RectAbsolute {
+            x1: -state.map_pos.x,
+            y1: -state.map_pos.y,
+            x2: (self.layout.map.x2 + tile_size.width() - 1) / tile_size.width() - state.map_pos.x,
+            y2: (self.layout.map.y2 + tile_size.height() - 1) / tile_size.height()
+                - state.map_pos.y,
+        };
 
-        self.screen
-            .draw(&sprite, state.map_pos.clone().into());
+        let map_rect = RectAbsolute {
+            x1: 0,
+            y1: 0,
+            x2: state.map.size.width(),
+            y2: state.map.size.height(),
+        };
+
+        let visible = intersect(&screen_rect, &map_rect);
+        let darkness =
+            Sprite::from_color_text(&" ".repeat(tile_size.width() as usize), Color::null());
+
+        for y in visible.y1..visible.y2 {
+            for x in visible.x1..visible.x2 {
+                let point = MapPoint::new(x, y);
+                let screen_point = self.to_screen(&(&point + &state.map_pos));
+                let visibility = state.get_visibility(&point);
+
+                if visibility == Visibility::Unexplored {
+                    self.screen
+                        .draw(&darkness, screen_point);
+                    continue;
+                }
+
+                let tile = state.map.get_tile(&point).unwrap();
+                let base_tile = state.tile_config.get(tile.tile_id);
+
+                let sprite = match self.zoom {
+                    Zoom::Normal => tile
+                        .get_animation(base_tile)
+                        .get_sprite(state.elapsed_time, tile.animation_phase),
+                    Zoom::Compact => base_tile.get_glyph(tile.variant),
+                };
+
+                let shade = match visibility {
+                    Visibility::Remembered => Shade::Remembered,
+                    _ => Shade::Lit(state.get_light_level(&point)),
+                };
+
+                let shaded = self.shade_sprite(sprite, shade);
+                self.screen.draw(&shaded, screen_point);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::terminal::Headless;
+
+    /// Compares `actual` with the stored snapshot `name`. Run the tests with
+    /// `UPDATE_SNAPSHOTS=1` to store the current output instead.
+    fn assert_snapshot(name: &str, actual: &str) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/renderer/snapshots")
+            .join(name);
+
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            fs::write(&path, actual).unwrap();
+            return;
+        }
+
+        let expected = fs::read_to_string(&path)
+            .unwrap_or_else(|_| panic!("snapshot {name} is missing, run with UPDATE_SNAPSHOTS=1"));
+
+        assert!(
+            actual == expected,
+            "snapshot {name} differs, run with UPDATE_SNAPSHOTS=1 to update it\n{actual}"
+        );
+    }
+
+    /// A state which always renders the same, without random tile animations.
+    fn get_state() -> State {
+        let mut state = State::new();
+
+        for tile in &mut Rc::make_mut(&mut state.map).tiles {
+            tile.animation_index = 0;
+            tile.animation_phase = 0;
+        }
+
+        for npc in &mut state.npcs {
+            npc.animation_phase = 0;
+        }
+
+        state
+    }
+
+    #[test]
+    fn test_display_example_map_compact() {
+        let mut renderer = Renderer::with_terminal(Headless::new(ScreenPoint::new(80, 30)));
+        renderer.zoom = Zoom::Compact;
+        let mut state = get_state();
+        state.map_pos = MapPoint::new(1, 1);
+
+        state.resize(&renderer.resize());
+        renderer.display(&state);
+
+        assert_snapshot("example_map_compact.txt", &renderer.screen.to_text());
+    }
+
+    #[test]
+    fn test_display_example_map() {
+        let terminal = Headless::new(ScreenPoint::new(120, 32));
+        let output = terminal.output.clone();
+        let mut renderer = Renderer::with_terminal(terminal);
+        let mut state = get_state();
+        state.map_pos = MapPoint::new(1, 1);
+
+        state.resize(&renderer.resize());
+        renderer.display(&state);
+
+        assert_snapshot("example_map.txt", &renderer.screen.to_text());
+        assert_snapshot(
+            "example_map.ansi",
+            &String::from_utf8(output.borrow().clone()).unwrap(),
+        );
+    }
+
+    #[test]
+    fn test_sidebar_hides_what_was_not_seen() {
+        let mut renderer = Renderer::with_terminal(Headless::new(ScreenPoint::new(120, 32)));
+        let mut state = get_state();
+        let ghost_pos = state.npcs[3].pos.clone();
+        state.map_pos = MapPoint::new(1, 1);
+
+        state.resize(&renderer.resize());
+        state.cursor_pos = &ghost_pos + &state.map_pos;
+        renderer.display(&state);
+
+        let text = renderer.screen.to_text();
+        assert!(text.contains("unexplored"));
+        assert!(!text.contains("tile: "));
+        assert!(!text.contains("npc: Ghost"));
+
+        let i = (state.map.size.width() * ghost_pos.y + ghost_pos.x) as usize;
+        Rc::make_mut(&mut state.map).visibility[i] = Visibility::Remembered;
+        renderer.display(&state);
+
+        let text = renderer.screen.to_text();
+        assert!(text.contains("tile: Dirt Floor"));
+        assert!(!text.contains("npc: Ghost"));
     }
 }
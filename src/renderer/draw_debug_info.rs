@@ -3,10 +3,12 @@ use crate::common::MapPoint;
 use crate::common::ScreenPoint;
 use crate::screen::Sprite;
 use crate::state::State;
+use crate::style::Style;
+use crate::terminal::Terminal;
 
 pub const DEBUG_INFO_PAGE_TOTAL: i32 = 2;
 
-impl Renderer {
+impl<W: Terminal> Renderer<W> {
     pub fn draw_debug_info(&mut self, state: &State) {
         match state.debug_info_page {
             0 => (),
@@ -26,11 +28,15 @@ impl Renderer {
     fn draw_page_info(&mut self, state: &State, text: &str) {
         self.debug_line_y = 0;
 
-        self.draw_next_line(format!(
-            //"\x1b[1m{}/{} {}\x1b[22m",
+        let sprite = Sprite::from(format!(
             "{}/{} {}",
             state.debug_info_page, DEBUG_INFO_PAGE_TOTAL, text,
-        ));
+        ))
+        .with_style(Style::bold());
+
+        self.screen
+            .draw(&sprite, ScreenPoint::new(0, self.debug_line_y));
+        self.debug_line_y += 1;
     }
 
     fn draw_debug_info_general(&mut self, state: &State) {
@@ -51,7 +57,10 @@ impl Renderer {
             state.map_pos.y,
             state.cursor_pos.x,
             state.cursor_pos.y,
-            state.astar_path.len(),
+            state
+                .astar_path
+                .as_ref()
+                .map(|p| p.len()),
         ));
 
         self.draw_next_line(format!(
@@ -76,6 +85,7 @@ impl Renderer {
 
     fn draw_debug_info_tasks(&mut self, state: &State) {
         self.draw_page_info(state, "Tasks");
+        self.draw_next_line(format!("path requests: {}", state.path_queue.len()));
         for task in &state.cursor_tasks {
             self.draw_next_line(format!("{}", task));
         }
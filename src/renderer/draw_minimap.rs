@@ -0,0 +1,148 @@
+use super::shade::Shade;
+use super::Renderer;
+use crate::color::Color;
+use crate::color::ColorValue;
+use crate::common::MapPoint;
+use crate::common::ScreenPoint;
+use crate::screen::Pixel;
+use crate::screen::Sprite;
+use crate::state::visibility::Visibility;
+use crate::state::State;
+use crate::style::Style;
+use crate::terminal::Terminal;
+
+const DIG_COLOR: ColorValue = ColorValue::Indexed(32);
+const VIEWPORT_COLOR: ColorValue = ColorValue::Indexed(15);
+
+impl<W: Terminal> Renderer<W> {
+    /// Draws the whole map into the minimap panel, one cell per tile if it fits,
+    /// otherwise one cell per square of `scale` x `scale` tiles.
+    pub fn draw_minimap(&mut self, state: &State) {
+        let panel = self.layout.minimap.clone();
+        let panel_size = panel.size();
+
+        if panel_size.width() <= 0 || panel_size.height() <= 0 {
+            return;
+        }
+
+        let map_size = &state.map.size;
+        let scale = div_ceil(map_size.width(), panel_size.width())
+            .max(div_ceil(map_size.height(), panel_size.height()))
+            .max(1);
+
+        let size = ScreenPoint::new(
+            div_ceil(map_size.width(), scale),
+            div_ceil(map_size.height(), scale),
+        );
+
+        let mut pixels = Vec::new();
+
+        for y in 0..size.height() {
+            for x in 0..size.width() {
+                pixels.push(self.get_minimap_pixel(
+                    state,
+                    &MapPoint::new(x * scale, y * scale),
+                    scale,
+                ));
+            }
+        }
+
+        let origin = ScreenPoint::new(panel.x1, panel.y1);
+        self.screen
+            .draw(&Sprite { pixels, size }, origin.clone());
+
+        self.draw_minimap_viewport(state, &origin, scale);
+    }
+
+    /// The pixel of the square of tiles starting at `corner`. Npcs are shown above
+    /// dig designations, which are shown above the tile color. The visibility of
+    /// the square is the one of its corner.
+    fn get_minimap_pixel(&mut self, state: &State, corner: &MapPoint, scale: i32) -> Pixel {
+        let points: Vec<MapPoint> = (0..scale)
+            .flat_map(|y| (0..scale).map(move |x| MapPoint::new(corner.x + x, corner.y + y)))
+            .collect();
+
+        let tile_color = match state.get_visibility(corner) {
+            Visibility::Unexplored => Color::null(),
+            Visibility::Remembered => state
+                .get_base_tile_at(corner)
+                .map(|t| self.shade_color(t.color, Shade::Remembered))
+                .unwrap_or(Color::null()),
+            Visibility::Visible => state
+                .get_base_tile_at(corner)
+                .map(|t| t.color)
+                .unwrap_or(Color::null()),
+        };
+        let bg_color = tile_color
+            .bg_color
+            .or(tile_color.fg_color);
+
+        if let Some(npc_index) = points
+            .iter()
+            .filter_map(|p| state.occupancy.get(p))
+            .find(|i| state.is_npc_visible(&state.npcs[*i]))
+        {
+            let base_npc = state.get_base_npc(&state.npcs[npc_index]);
+
+            return Pixel {
+                ch: '@',
+                color: Color {
+                    bg_color,
+                    fg_color: base_npc.color.fg_color,
+                },
+                style: Style::bold(),
+            };
+        }
+
+        let bg_color = if points
+            .iter()
+            .any(|p| state.dig_selection.contains(p))
+        {
+            Some(DIG_COLOR)
+        } else {
+            bg_color
+        };
+
+        Pixel {
+            ch: ' ',
+            color: Color {
+                bg_color,
+                fg_color: None,
+            },
+            style: Style::default(),
+        }
+    }
+
+    /// Outlines the part of the map visible in the map viewport.
+    fn draw_minimap_viewport(&mut self, state: &State, origin: &ScreenPoint, scale: i32) {
+        let x1 = (-state.map_pos.x).div_euclid(scale);
+        let y1 = (-state.map_pos.y).div_euclid(scale);
+        let x2 = (state.screen_size.width() - state.map_pos.x - 1).div_euclid(scale);
+        let y2 = (state.screen_size.height() - state.map_pos.y - 1).div_euclid(scale);
+
+        let color = Color {
+            bg_color: None,
+            fg_color: Some(VIEWPORT_COLOR),
+        };
+
+        for y in y1..=y2 {
+            for x in x1..=x2 {
+                let ch = match (x == x1 || x == x2, y == y1 || y == y2) {
+                    (true, true) => '+',
+                    (false, true) => '-',
+                    (true, false) => '|',
+                    (false, false) => continue,
+                };
+
+                self.screen.draw(
+                    &Sprite::from_color_text(&ch.to_string(), color),
+                    origin + &ScreenPoint::new(x, y),
+                );
+            }
+        }
+    }
+}
+
+fn div_ceil(a: i32, b: i32) -> i32 {
+    (a + b - 1) / b
+}
@@ -1,29 +1,35 @@
 use super::Renderer;
-use crate::screen::{Animation, Sprite};
+use crate::common::Zoom;
+use crate::screen::Animation;
+use crate::screen::Sprite;
+use crate::state::npc::Npc;
 use crate::state::npc::NpcAnimationId;
 use crate::state::State;
+use crate::terminal::Terminal;
 
-impl Renderer {
+impl<W: Terminal> Renderer<W> {
     pub fn draw_npcs(&mut self, state: &State) {
         for npc in &state.npcs {
+            if !state.is_npc_visible(npc) {
+                continue;
+            }
+
             let base_npc = &state.get_base_npc(npc);
 
-            let sprite = match npc.animation {
-                NpcAnimationId::Idle => Renderer::get_sprite(state, &base_npc.animation_idle),
-                NpcAnimationId::Walk => Renderer::get_sprite(state, &base_npc.animation_walk),
-                NpcAnimationId::Run => todo!(),
-                NpcAnimationId::Attack => todo!(),
+            let sprite = match (self.zoom, &npc.animation) {
+                (Zoom::Compact, _) => &base_npc.glyph,
+                (_, NpcAnimationId::Idle) => Self::get_sprite(state, npc, &base_npc.animation_idle),
+                (_, NpcAnimationId::Walk) => Self::get_sprite(state, npc, &base_npc.animation_walk),
+                (_, NpcAnimationId::Run) => todo!(),
+                (_, NpcAnimationId::Attack) => todo!(),
             };
 
-            self.screen
-                .draw(&sprite, (&npc.pos + &state.map_pos).into());
+            let screen_point = self.to_screen(&(&npc.pos + &state.map_pos));
+            self.screen.draw(sprite, screen_point);
         }
     }
 
-    fn get_sprite<'a>(state: &'a State, animations: &'a Vec<Animation>) -> &'a Sprite {
-        let sprites = &animations[0].sprites;
-        let frame = (state.elapsed_time % sprites.len() as u64) as usize;
-
-        &sprites[frame]
+    fn get_sprite<'a>(state: &State, npc: &Npc, animations: &'a [Animation]) -> &'a Sprite {
+        animations[0].get_sprite(state.elapsed_time, npc.animation_phase)
     }
 }
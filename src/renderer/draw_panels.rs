@@ -0,0 +1,161 @@
+use super::Renderer;
+use crate::color::Color;
+use crate::common::RectAbsolute;
+use crate::common::ScreenPoint;
+use crate::screen::Sprite;
+use crate::state::message_log::Severity;
+use crate::state::visibility::Visibility;
+use crate::state::State;
+use crate::style::Style;
+use crate::terminal::Terminal;
+
+const PANEL_COLOR: Color = Color::new(235, 250);
+const TITLE_COLOR: Color = Color::new(235, 214);
+
+impl<W: Terminal> Renderer<W> {
+    pub fn draw_sidebar(&mut self, state: &State) {
+        let panel = self.layout.sidebar.clone();
+        let mut lines = Vec::new();
+
+        let cursor_map_pos = &state.cursor_pos - &state.map_pos;
+        lines.push(format!("x: {}, y: {}", cursor_map_pos.x, cursor_map_pos.y));
+
+        if state.get_visibility(&cursor_map_pos) == Visibility::Unexplored {
+            lines.push(String::from("unexplored"));
+            self.draw_panel(&panel, "Info", &lines);
+            return;
+        }
+
+        if let Some(base_tile) = state.get_base_tile_at(&cursor_map_pos) {
+            lines.push(format!("tile: {}", base_tile.name));
+            if base_tile.minable {
+                lines.push(String::from("minable"));
+            }
+        }
+
+        let visible_npc = state
+            .occupancy
+            .get(&cursor_map_pos)
+            .map(|i| &state.npcs[i])
+            .filter(|npc| state.is_npc_visible(npc));
+
+        if let Some(npc) = visible_npc {
+            let base_npc = state.get_base_npc(npc);
+
+            lines.push(String::new());
+            lines.push(format!("npc: {}", base_npc.name));
+            lines.push(format!("class: {:?}", base_npc.npc_class));
+            lines.push(format!("task: {}", npc.task));
+        }
+
+        self.draw_panel(&panel, "Info", &lines);
+    }
+
+    pub fn draw_message_log(&mut self, state: &State) {
+        let panel = self.layout.message_log.clone();
+
+        self.draw_messages(&panel, "Messages", state, 0);
+    }
+
+    /// Draws the message log over the map viewport, scrolled by the player.
+    pub fn draw_message_scrollback(&mut self, state: &State) {
+        if !state.message_log.is_open {
+            return;
+        }
+
+        let panel = self.layout.map.clone();
+        let title = format!(
+            "Messages ({}/{}, o: close, page up/down: scroll)",
+            state.message_log.scroll,
+            state.message_log.len(),
+        );
+
+        self.draw_messages(&panel, &title, state, state.message_log.scroll);
+    }
+
+    pub fn draw_status_bar(&mut self, state: &State) {
+        let panel = self.layout.status_bar.clone();
+        let task_total =
+            state.cursor_tasks.len() + state.soldier_tasks.len() + state.worker_tasks.len();
+
+        let text = format!(
+            " time: {} | npcs: {} | tasks: {} | dig: {} | path requests: {}",
+            state.elapsed_time,
+            state.npcs.len(),
+            task_total,
+            state.dig_selection.len(),
+            state.path_queue.len(),
+        );
+
+        self.fill_panel(&panel, TITLE_COLOR);
+        self.screen.draw(
+            &Sprite::from_color_text(&text, TITLE_COLOR),
+            ScreenPoint::new(panel.x1, panel.y1),
+        );
+    }
+
+    /// Draws a panel with a bold title in its first row and `lines` below,
+    /// lines not fitting into the panel are cut off.
+    fn draw_panel(&mut self, panel: &RectAbsolute<i32>, title: &str, lines: &[String]) {
+        self.fill_panel(panel, PANEL_COLOR);
+
+        self.screen.draw(
+            &Sprite::from_color_text(&format!(" {title}"), TITLE_COLOR).with_style(Style::bold()),
+            ScreenPoint::new(panel.x1, panel.y1),
+        );
+
+        for (i, line) in lines.iter().enumerate() {
+            self.screen.draw(
+                &Sprite::from_color_text(&format!(" {line}"), PANEL_COLOR),
+                ScreenPoint::new(panel.x1, panel.y1 + 1 + i as i32),
+            );
+        }
+    }
+
+    fn draw_messages(
+        &mut self,
+        panel: &RectAbsolute<i32>,
+        title: &str,
+        state: &State,
+        scroll: usize,
+    ) {
+        self.draw_panel(panel, title, &[]);
+
+        let count = (panel.size().height() - 1).max(0) as usize;
+        let messages = state
+            .message_log
+            .get_page(count, scroll);
+
+        for (i, message) in messages.enumerate() {
+            let color = Color {
+                bg_color: PANEL_COLOR.bg_color,
+                fg_color: get_severity_color(message.severity).fg_color,
+            };
+
+            self.screen.draw(
+                &Sprite::from_color_text(&format!(" {message}"), color),
+                ScreenPoint::new(panel.x1, panel.y1 + 1 + i as i32),
+            );
+        }
+    }
+
+    fn fill_panel(&mut self, panel: &RectAbsolute<i32>, color: Color) {
+        let size = panel.size();
+        let text = " ".repeat(size.width().max(0) as usize);
+
+        for y in panel.y1..panel.y2 {
+            self.screen.draw(
+                &Sprite::from_color_text(&text, color),
+                ScreenPoint::new(panel.x1, y),
+            );
+        }
+    }
+}
+
+fn get_severity_color(severity: Severity) -> Color {
+    match severity {
+        Severity::Info => PANEL_COLOR,
+        Severity::Warning => Color::new(235, 214),
+        Severity::Error => Color::new(235, 196),
+    }
+}
@@ -0,0 +1,98 @@
+use crate::common::RectAbsolute;
+use crate::common::ScreenPoint;
+
+pub const SIDEBAR_WIDTH: i32 = 32;
+pub const MINIMAP_HEIGHT: i32 = 16;
+pub const MESSAGE_LOG_HEIGHT: i32 = 5;
+pub const STATUS_BAR_HEIGHT: i32 = 1;
+
+/// The areas of the screen the renderer draws into.
+///
+/// ```text
+/// +-------------+---------+
+/// | map         | sidebar |
+/// |             |         |
+/// |             +---------+
+/// +-------------+ minimap |
+/// | message log |         |
+/// +-------------+---------+
+/// | status bar            |
+/// +-----------------------+
+/// ```
+#[derive(Clone, Debug)]
+pub struct Layout {
+    pub map: RectAbsolute<i32>,
+    pub sidebar: RectAbsolute<i32>,
+    pub minimap: RectAbsolute<i32>,
+    pub message_log: RectAbsolute<i32>,
+    pub status_bar: RectAbsolute<i32>,
+}
+
+impl Layout {
+    /// Splits a screen of `size`, panels shrink on small screens so the map stays visible.
+    pub fn new(size: &ScreenPoint) -> Self {
+        let sidebar_width = SIDEBAR_WIDTH.min(size.width() / 3);
+        let status_bar_height = STATUS_BAR_HEIGHT.min(size.height());
+        let message_log_height = MESSAGE_LOG_HEIGHT.min(size.height() / 4);
+
+        let map_x2 = size.width() - sidebar_width;
+        let status_bar_y1 = size.height() - status_bar_height;
+        let message_log_y1 = status_bar_y1 - message_log_height;
+        let minimap_y1 = status_bar_y1 - MINIMAP_HEIGHT.min(status_bar_y1 / 2);
+
+        Self {
+            map: RectAbsolute {
+                x1: 0,
+                y1: 0,
+                x2: map_x2,
+                y2: message_log_y1,
+            },
+            sidebar: RectAbsolute {
+                x1: map_x2,
+                y1: 0,
+                x2: size.width(),
+                y2: minimap_y1,
+            },
+            minimap: RectAbsolute {
+                x1: map_x2,
+                y1: minimap_y1,
+                x2: size.width(),
+                y2: status_bar_y1,
+            },
+            message_log: RectAbsolute {
+                x1: 0,
+                y1: message_log_y1,
+                x2: map_x2,
+                y2: status_bar_y1,
+            },
+            status_bar: RectAbsolute {
+                x1: 0,
+                y1: status_bar_y1,
+                x2: size.width(),
+                y2: size.height(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_covers_screen() {
+        let layout = Layout::new(&ScreenPoint::new(120, 40));
+
+        assert_eq!(layout.map.size(), ScreenPoint::new(88, 34));
+        assert_eq!(layout.sidebar.size(), ScreenPoint::new(32, 23));
+        assert_eq!(layout.minimap.size(), ScreenPoint::new(32, 16));
+        assert_eq!(layout.message_log.size(), ScreenPoint::new(88, 5));
+        assert_eq!(layout.status_bar.size(), ScreenPoint::new(120, 1));
+
+        let layout = Layout::new(&ScreenPoint::new(30, 8));
+
+        assert_eq!(layout.map.size(), ScreenPoint::new(20, 5));
+        assert_eq!(layout.sidebar.size(), ScreenPoint::new(10, 4));
+        assert_eq!(layout.minimap.size(), ScreenPoint::new(10, 3));
+    }
+}
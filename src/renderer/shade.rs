@@ -0,0 +1,96 @@
+use super::Renderer;
+use crate::color::Color;
+use crate::color::ColorValue;
+use crate::screen::Sprite;
+use crate::terminal::Terminal;
+
+/// How bright remembered tiles out of sight are drawn.
+const REMEMBERED_BRIGHTNESS: f32 = 0.4;
+/// How bright tiles in sight but without any light are drawn.
+const UNLIT_BRIGHTNESS: f32 = 0.6;
+/// The color of light, lit tiles are tinted with.
+const LIGHT_COLOR: ColorValue = ColorValue::Rgb(255, 170, 60);
+/// How strongly a tile in full light is tinted with the light color.
+const LIGHT_STRENGTH: f32 = 0.35;
+/// The light level from which on tiles are drawn the brightest.
+const FULL_LIGHT_LEVEL: i32 = 3;
+
+/// How the colors of a tile are changed. Tiles in sight are dimmed by the
+/// light level, from `UNLIT_BRIGHTNESS` up to their full colors tinted by the
+/// light at `FULL_LIGHT_LEVEL`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Shade {
+    Remembered,
+    Lit(i32),
+}
+
+impl Shade {
+    fn apply(self, color: ColorValue) -> ColorValue {
+        match self {
+            Shade::Remembered => color.darken(REMEMBERED_BRIGHTNESS),
+            Shade::Lit(level) => {
+                let light = level.clamp(0, FULL_LIGHT_LEVEL) as f32 / FULL_LIGHT_LEVEL as f32;
+                color
+                    .darken(UNLIT_BRIGHTNESS + (1.0 - UNLIT_BRIGHTNESS) * light)
+                    .blend(LIGHT_COLOR, LIGHT_STRENGTH * light)
+            }
+        }
+    }
+}
+
+impl<W: Terminal> Renderer<W> {
+    /// The shaded color, downsampled to what the terminal displays. Shading the
+    /// same few colors every frame is cached, to avoid searching the palette.
+    pub fn shade_color(&mut self, color: Color, shade: Shade) -> Color {
+        let color_mode = self.screen.color_mode;
+
+        *self
+            .shades
+            .entry((color, shade))
+            .or_insert_with(|| Color {
+                bg_color: color
+                    .bg_color
+                    .map(|c| shade.apply(c).downsample(color_mode)),
+                fg_color: color
+                    .fg_color
+                    .map(|c| shade.apply(c).downsample(color_mode)),
+            })
+    }
+
+    pub fn shade_sprite(&mut self, sprite: &Sprite, shade: Shade) -> Sprite {
+        let mut shaded = sprite.clone();
+        for pixel in &mut shaded.pixels {
+            pixel.color = self.shade_color(pixel.color, shade);
+        }
+        shaded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_brightness(color: ColorValue) -> u32 {
+        let (r, g, b) = color.to_rgb();
+        r as u32 + g as u32 + b as u32
+    }
+
+    #[test]
+    fn test_shade_by_light_level() {
+        let color = ColorValue::Rgb(120, 120, 120);
+
+        let remembered = get_brightness(Shade::Remembered.apply(color));
+        let unlit = get_brightness(Shade::Lit(0).apply(color));
+        let dim = get_brightness(Shade::Lit(1).apply(color));
+        let lit = get_brightness(Shade::Lit(FULL_LIGHT_LEVEL).apply(color));
+
+        assert!(remembered < unlit);
+        assert!(unlit < dim);
+        assert!(dim < lit);
+        assert!(unlit < get_brightness(color));
+        assert_eq!(
+            Shade::Lit(FULL_LIGHT_LEVEL + 2).apply(color),
+            Shade::Lit(FULL_LIGHT_LEVEL).apply(color)
+        );
+    }
+}
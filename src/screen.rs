@@ -1,21 +1,42 @@
-use std::io::stdout;
-use std::io::Stdout;
-use std::io::Write;
-
-use termion::raw::IntoRawMode;
-use termion::raw::RawTerminal;
-
 use crate::color::Color;
+use crate::color::ColorMode;
+use crate::color::ColorValue;
 use crate::common::intersect;
 use crate::common::RectAbsolute;
 use crate::common::ScreenPoint;
+use crate::style::Style;
+use crate::terminal::Terminal;
 
-pub type DefaultScreen = Screen<RawTerminal<Stdout>>;
-
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Pixel {
     pub ch: char,
     pub color: Color,
+    pub style: Style,
+}
+
+impl Pixel {
+    /// Composites `self` over `below`. Missing colors are taken from `below`,
+    /// and a space without a background leaves `below` visible.
+    pub fn over(&self, below: &Pixel) -> Pixel {
+        if self.ch == ' ' && self.color.bg_color.is_none() {
+            return below.clone();
+        }
+
+        Pixel {
+            ch: self.ch,
+            color: Color {
+                bg_color: self
+                    .color
+                    .bg_color
+                    .or(below.color.bg_color),
+                fg_color: self
+                    .color
+                    .fg_color
+                    .or(below.color.fg_color),
+            },
+            style: self.style,
+        }
+    }
 }
 
 impl From<char> for Pixel {
@@ -23,6 +44,7 @@ impl From<char> for Pixel {
         Self {
             color: Color::text(),
             ch,
+            style: Style::default(),
         }
     }
 }
@@ -40,7 +62,11 @@ impl Sprite {
         let mut pixels = Vec::new();
 
         for ch in text.chars() {
-            pixels.push(Pixel { ch, color });
+            pixels.push(Pixel {
+                ch,
+                color,
+                style: Style::default(),
+            });
         }
 
         Self {
@@ -48,6 +74,58 @@ impl Sprite {
             size: ScreenPoint::new(width, height),
         }
     }
+
+    /// Reads text in which `{fg}`, `{fg:bg}` or `{:bg}` set the colors of the
+    /// following characters and `{}` resets them, e.g. `"{196}~{208:52}~{}~"`.
+    /// Colors are palette indices or `#rrggbb`, `{{` is a literal `{`.
+    pub fn from_markup(text: &str) -> Self {
+        let mut pixels = Vec::new();
+        let mut color = Color::none();
+        let mut chars = text.chars().peekable();
+
+        while let Some(ch) = chars.next() {
+            if ch != '{' || chars.next_if_eq(&'{').is_some() {
+                pixels.push(Pixel {
+                    ch,
+                    color,
+                    style: Style::default(),
+                });
+                continue;
+            }
+
+            let markup: String = chars
+                .by_ref()
+                .take_while(|c| *c != '}')
+                .collect();
+            let (fg_color, bg_color) = markup
+                .split_once(':')
+                .unwrap_or((&markup, ""));
+
+            let parse = |s: &str| {
+                (!s.is_empty()).then(|| {
+                    ColorValue::parse_str(s)
+                        .unwrap_or_else(|e| panic!("Color markup in '{text}' is invalid: {e:?}."))
+                })
+            };
+
+            color = Color {
+                bg_color: parse(bg_color),
+                fg_color: parse(fg_color),
+            };
+        }
+
+        Self {
+            size: ScreenPoint::new(pixels.len() as i32, 1),
+            pixels,
+        }
+    }
+
+    pub fn with_style(mut self, style: Style) -> Self {
+        for pixel in &mut self.pixels {
+            pixel.style = style;
+        }
+        self
+    }
 }
 
 impl From<&str> for Sprite {
@@ -68,74 +146,141 @@ impl From<String> for Sprite {
 #[derive(Clone, Debug)]
 pub struct Animation {
     pub sprites: Vec<Sprite>,
+    /// How many ticks each sprite is shown.
+    pub durations: Vec<u64>,
+    /// Whether tiles and npcs showing the animation start at different frames.
+    pub random_phase: bool,
 }
 
 impl Animation {
     pub fn new(texts: Vec<&str>, color: Color) -> Self {
-        let sprites = texts
+        let sprites: Vec<Sprite> = texts
             .into_iter()
             .map(|t| Sprite::from_color_text(t, color))
             .collect();
 
-        Self { sprites }
+        Self {
+            durations: vec![1; sprites.len()],
+            sprites,
+            random_phase: false,
+        }
     }
 
+    /// Colors the pixels which got no color from the markup of their frame.
     pub fn with_color(color: Color) -> impl Fn(Self) -> Self {
         move |mut animation| {
             for sprite_i in 0..animation.sprites.len() {
                 let sprite = &mut animation.sprites[sprite_i];
                 for pixel_i in 0..sprite.pixels.len() {
-                    sprite.pixels[pixel_i].color = color;
+                    let pixel_color = &mut sprite.pixels[pixel_i].color;
+                    pixel_color.bg_color = pixel_color.bg_color.or(color.bg_color);
+                    pixel_color.fg_color = pixel_color.fg_color.or(color.fg_color);
                 }
             }
             animation
         }
     }
+
+    /// The sprite shown at `elapsed_time`. `phase` offsets the time if the
+    /// animation has a random phase.
+    pub fn get_sprite(&self, elapsed_time: u64, phase: u64) -> &Sprite {
+        let total: u64 = self.durations.iter().sum();
+        let phase = if self.random_phase { phase } else { 0 };
+        let mut time = elapsed_time.wrapping_add(phase) % total.max(1);
+
+        for (sprite, duration) in self.sprites.iter().zip(&self.durations) {
+            if time < *duration {
+                return sprite;
+            }
+            time -= duration;
+        }
+
+        &self.sprites[0]
+    }
 }
 
 impl From<Vec<&str>> for Animation {
     fn from(f: Vec<&str>) -> Self {
-        let sprites = f
-            .into_iter()
-            .map(|t| Sprite::from_color_text(t, Color::none()))
-            .collect();
-
-        Self { sprites }
+        Animation::new(f, Color::none())
     }
 }
 
+/// Reads either an array of frames or a table like
+/// `{ frames = [...], frame_duration = 4, random_phase = true }`.
+/// Each frame is a markup string, see `Sprite::from_markup`, or a table like
+/// `{ text = "~  ", style = ["bold"], duration = 2 }`.
 impl From<&toml::Value> for Animation {
     fn from(value: &toml::Value) -> Self {
-        let str_vec: Vec<&str> = value
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|x| x.as_str().unwrap())
-            .collect();
+        let (frames, frame_duration, random_phase) = match value.as_table() {
+            Some(t) => (
+                &t["frames"],
+                t.get("frame_duration")
+                    .and_then(|d| d.as_integer())
+                    .map(|d| d as u64)
+                    .unwrap_or(1),
+                t.get("random_phase")
+                    .and_then(|r| r.as_bool())
+                    .unwrap_or(false),
+            ),
+            None => (value, 1, false),
+        };
+
+        let mut sprites = Vec::new();
+        let mut durations = Vec::new();
 
-        Animation::from(str_vec)
+        for frame in frames.as_array().unwrap() {
+            if let Some(text) = frame.as_str() {
+                sprites.push(Sprite::from_markup(text));
+                durations.push(frame_duration);
+                continue;
+            }
+
+            let style = frame
+                .get("style")
+                .map(Style::from)
+                .unwrap_or_default();
+            let duration = frame
+                .get("duration")
+                .and_then(|d| d.as_integer())
+                .map(|d| d as u64)
+                .unwrap_or(frame_duration);
+
+            sprites.push(Sprite::from_markup(frame["text"].as_str().unwrap()).with_style(style));
+            durations.push(duration);
+        }
+
+        Self {
+            sprites,
+            durations,
+            random_phase,
+        }
     }
 }
 
-pub struct Screen<W: Write> {
+pub struct Screen<W: Terminal> {
     main_display: W,
     prelude_buffer: String,
 
     // TODO: make sprite?
     pixel_buffer: Vec<Pixel>,
 
+    /// What the terminal currently shows, with inherited colors resolved.
+    displayed_buffer: Vec<Pixel>,
+    displayed_color: Color,
+    displayed_size: ScreenPoint,
+    displayed_style: Style,
+
+    pub color_mode: ColorMode,
+
+    /// Drawing is clipped to this area of the screen.
+    viewport: RectAbsolute<i32>,
+
     pub size: ScreenPoint,
 }
 
-impl DefaultScreen {
-    pub fn new() -> Self {
-        Screen::from(stdout().into_raw_mode().unwrap())
-    }
-
+impl<W: Terminal> Screen<W> {
     pub fn resize(&mut self) -> ScreenPoint {
-        let (cols, rows) = termion::terminal_size().unwrap();
-
-        self.size = ScreenPoint::new(cols as i32, rows as i32);
+        self.size = self.main_display.size();
 
         self.size.clone()
     }
@@ -144,19 +289,28 @@ impl DefaultScreen {
         let buffer_size = (self.size.width() * self.size.height()) as usize;
         self.prelude_buffer = String::new();
         self.pixel_buffer = vec![Pixel::from(' '); buffer_size];
+        self.reset_viewport();
 
         //self.prelude_buffer.push_str("\x1b[2J"); // clear screen
         //self.prelude_buffer.push_str("\x1b[H"); // goto to (1, 1)
     }
 
-    pub fn draw_color(&mut self, pos: ScreenPoint, size: ScreenPoint, color: Color) {
-        let screen_rect = RectAbsolute {
+    /// Clips all drawing to `viewport` until the next call or `reset_viewport`.
+    pub fn set_viewport(&mut self, viewport: &RectAbsolute<i32>) {
+        self.reset_viewport();
+        self.viewport = intersect(&self.viewport, viewport);
+    }
+
+    pub fn reset_viewport(&mut self) {
+        self.viewport = RectAbsolute {
             x1: 0,
             y1: 0,
             x2: self.size.width(),
             y2: self.size.height(),
         };
+    }
 
+    pub fn draw_color(&mut self, pos: ScreenPoint, size: ScreenPoint, color: Color) {
         let sprite_rect = RectAbsolute {
             x1: pos.x,
             y1: pos.y,
@@ -164,26 +318,17 @@ impl DefaultScreen {
             y2: pos.y + size.height(),
         };
 
-        let intersection = intersect(&screen_rect, &sprite_rect);
+        let intersection = intersect(&self.viewport, &sprite_rect);
 
         for sprite_y in intersection.y1..intersection.y2 {
             for sprite_x in intersection.x1..intersection.x2 {
                 let screen_i = (self.size.width() * sprite_y + sprite_x) as usize;
-                let ch = self.pixel_buffer[screen_i].ch;
-
-                self.pixel_buffer[screen_i] = Pixel { ch, color };
+                self.pixel_buffer[screen_i].color = color;
             }
         }
     }
 
     pub fn draw_inversion(&mut self, pos: ScreenPoint, size: ScreenPoint) {
-        let screen_rect = RectAbsolute {
-            x1: 0,
-            y1: 0,
-            x2: self.size.width(),
-            y2: self.size.height(),
-        };
-
         let sprite_rect = RectAbsolute {
             x1: pos.x,
             y1: pos.y,
@@ -191,7 +336,7 @@ impl DefaultScreen {
             y2: pos.y + size.height(),
         };
 
-        let intersection = intersect(&screen_rect, &sprite_rect);
+        let intersection = intersect(&self.viewport, &sprite_rect);
 
         for sprite_y in intersection.y1..intersection.y2 {
             for sprite_x in intersection.x1..intersection.x2 {
@@ -205,25 +350,13 @@ impl DefaultScreen {
                     .color
                     .fg_color;
 
-                let ch = self.pixel_buffer[screen_i].ch;
-
-                self.pixel_buffer[screen_i] = Pixel {
-                    ch,
-                    color: Color { fg_color, bg_color },
-                };
+                self.pixel_buffer[screen_i].color = Color { fg_color, bg_color };
             }
         }
     }
 
     // TODO: make p a reference
     pub fn draw(&mut self, sprite: &Sprite, p: ScreenPoint) {
-        let screen_rect = RectAbsolute {
-            x1: 0,
-            y1: 0,
-            x2: self.size.width(),
-            y2: self.size.height(),
-        };
-
         let sprite_rect = RectAbsolute {
             x1: p.x,
             y1: p.y,
@@ -231,53 +364,119 @@ impl DefaultScreen {
             y2: p.y + sprite.size.height(),
         };
 
-        let intersection = intersect(&screen_rect, &sprite_rect);
+        let intersection = intersect(&self.viewport, &sprite_rect);
 
         for sprite_y in intersection.y1..intersection.y2 {
             for sprite_x in intersection.x1..intersection.x2 {
                 let screen_i = (self.size.width() * sprite_y + sprite_x) as usize;
                 let sprite_i = (sprite.size.width() * (sprite_y - p.y) + sprite_x - p.x) as usize;
 
-                self.pixel_buffer[screen_i] = sprite.pixels[sprite_i].clone();
+                self.pixel_buffer[screen_i] =
+                    sprite.pixels[sprite_i].over(&self.pixel_buffer[screen_i]);
             }
         }
     }
 
+    /// The characters of the screen, one line per row.
+    #[cfg(test)]
+    pub fn to_text(&self) -> String {
+        self.pixel_buffer
+            .chunks(self.size.width() as usize)
+            .map(|row| {
+                row.iter()
+                    .map(|p| p.ch)
+                    .collect::<String>()
+                    + "\n"
+            })
+            .collect()
+    }
+
+    /// Writes the cells which changed since the last call, moving the cursor
+    /// only where unchanged cells are skipped.
     pub fn display(&mut self) {
         let mut s = String::new();
 
         s.push_str(&self.prelude_buffer);
 
-        for y in 0..self.size.height() {
-            let row = y + 1;
-            s.push_str(&format!("\x1b[{row};1H")); // goto (row, 1)
+        if self.displayed_size != self.size {
+            self.displayed_buffer = Vec::new();
+            self.displayed_color = Color::none();
+            self.displayed_size = self.size.clone();
+        }
 
-            let mut last_color = Color::none();
+        // pixels without a color keep the color of the pixel written before them
+        let mut inherited_color = Color::none();
+        let mut cursor = None;
 
-            // TODO: background color is not properly set, when e.g. transparent npc is next to another color
+        for y in 0..self.size.height() {
             for x in 0..self.size.width() {
                 let i = (self.size.width() * y + x) as usize;
-                let ch = self.pixel_buffer[i].ch;
-                let color = self.pixel_buffer[i].color;
+                let pixel = &self.pixel_buffer[i];
 
-                let mut change_color = Color::none();
+                inherited_color.bg_color = pixel
+                    .color
+                    .bg_color
+                    .or(inherited_color.bg_color);
+                inherited_color.fg_color = pixel
+                    .color
+                    .fg_color
+                    .or(inherited_color.fg_color);
+
+                let resolved = Pixel {
+                    ch: pixel.ch,
+                    color: inherited_color,
+                    style: pixel.style,
+                };
 
-                if color.bg_color.is_some() && color.bg_color != last_color.bg_color {
-                    change_color.bg_color = color.bg_color;
+                if self.displayed_buffer.get(i) == Some(&resolved) {
+                    continue;
                 }
 
-                if color.fg_color.is_some() && color.fg_color != last_color.fg_color {
-                    change_color.fg_color = color.fg_color;
+                if cursor != Some(i) {
+                    let row = y + 1;
+                    let column = x + 1;
+                    s.push_str(&format!("\x1b[{row};{column}H")); // goto (row, column)
                 }
 
-                s.push_str(&format!("{change_color}{ch}"));
+                let mut change_color = Color::none();
 
-                if change_color.bg_color.is_some() {
-                    last_color.bg_color = change_color.bg_color;
+                if resolved.color.bg_color != self.displayed_color.bg_color {
+                    change_color.bg_color = resolved.color.bg_color;
                 }
 
-                if change_color.fg_color.is_some() {
-                    last_color.fg_color = change_color.fg_color;
+                if resolved.color.fg_color != self.displayed_color.fg_color {
+                    change_color.fg_color = resolved.color.fg_color;
+                }
+
+                s.push_str(&change_color.escape(self.color_mode));
+                s.push_str(
+                    &self
+                        .displayed_style
+                        .transition(&resolved.style),
+                );
+                self.displayed_style = resolved.style;
+                s.push(resolved.ch);
+
+                self.displayed_color.bg_color = resolved
+                    .color
+                    .bg_color
+                    .or(self.displayed_color.bg_color);
+                self.displayed_color.fg_color = resolved
+                    .color
+                    .fg_color
+                    .or(self.displayed_color.fg_color);
+
+                // the cursor does not wrap reliably at the end of a row
+                cursor = if x + 1 < self.size.width() {
+                    Some(i + 1)
+                } else {
+                    None
+                };
+
+                if let Some(displayed) = self.displayed_buffer.get_mut(i) {
+                    *displayed = resolved;
+                } else {
+                    self.displayed_buffer.push(resolved);
                 }
             }
         }
@@ -289,7 +488,7 @@ impl DefaultScreen {
     }
 }
 
-impl<W: Write> From<W> for Screen<W> {
+impl<W: Terminal> From<W> for Screen<W> {
     fn from(mut buffer: W) -> Self {
         write!(
             buffer,
@@ -302,22 +501,33 @@ impl<W: Write> From<W> for Screen<W> {
 
         buffer.flush().unwrap();
 
-        let (cols, rows) = termion::terminal_size().unwrap();
-        let buffer_size = (cols * rows) as usize;
+        let size = buffer.size();
+        let buffer_size = (size.width() * size.height()) as usize;
 
         let prelude_buffer = String::new();
         let pixel_buffer = vec![Pixel::from(' '); buffer_size];
 
         Self {
-            main_display: buffer,
             prelude_buffer,
             pixel_buffer,
-            size: ScreenPoint::new(cols as i32, rows as i32),
+            displayed_buffer: Vec::new(),
+            displayed_color: Color::none(),
+            displayed_size: size.clone(),
+            displayed_style: Style::default(),
+            color_mode: buffer.color_mode(),
+            main_display: buffer,
+            viewport: RectAbsolute {
+                x1: 0,
+                y1: 0,
+                x2: size.width(),
+                y2: size.height(),
+            },
+            size,
         }
     }
 }
 
-impl<W: Write> Drop for Screen<W> {
+impl<W: Terminal> Drop for Screen<W> {
     fn drop(&mut self) {
         write!(
             self.main_display,
@@ -332,3 +542,136 @@ impl<W: Write> Drop for Screen<W> {
         self.main_display.flush().unwrap();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::Headless;
+
+    #[test]
+    fn test_draw_composites_transparent_pixels() {
+        let mut screen = Screen::from(Headless::new(ScreenPoint::new(3, 1)));
+        let transparent = Color {
+            bg_color: None,
+            fg_color: Some(ColorValue::Indexed(5)),
+        };
+
+        screen.draw(
+            &Sprite::from_color_text("abc", Color::new(1, 2)),
+            ScreenPoint::new(0, 0),
+        );
+        screen.draw(
+            &Sprite::from_color_text("x y", transparent),
+            ScreenPoint::new(0, 0),
+        );
+
+        assert_eq!(
+            screen.pixel_buffer,
+            vec![
+                Pixel {
+                    ch: 'x',
+                    color: Color::new(1, 5),
+                    style: Style::default(),
+                },
+                Pixel {
+                    ch: 'b',
+                    color: Color::new(1, 2),
+                    style: Style::default(),
+                },
+                Pixel {
+                    ch: 'y',
+                    color: Color::new(1, 5),
+                    style: Style::default(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_display_writes_changed_cells_only() {
+        let terminal = Headless::new(ScreenPoint::new(4, 2));
+        let output = terminal.output.clone();
+        let mut screen = Screen::from(terminal);
+
+        screen.draw(
+            &Sprite::from_color_text("ab", Color::text()),
+            ScreenPoint::new(0, 0),
+        );
+        screen.display();
+        output.borrow_mut().clear();
+
+        screen.display();
+        assert!(output.borrow().is_empty());
+
+        screen.draw(
+            &Sprite::from_color_text("x", Color::text()),
+            ScreenPoint::new(2, 1),
+        );
+        screen.draw(
+            &Sprite::from_color_text("y", Color::new(1, 2)),
+            ScreenPoint::new(3, 1),
+        );
+        screen.display();
+        assert_eq!(
+            String::from_utf8(output.borrow().clone()).unwrap(),
+            "\x1b[2;3Hx\x1b[38;5;2;48;5;1my"
+        );
+    }
+
+    #[test]
+    fn test_animation_frame_durations() {
+        let value: toml::Value = toml::from_str(
+            r#"animation = { frames = ["a", { text = "b", duration = 3 }], frame_duration = 2, random_phase = true }"#,
+        )
+        .unwrap();
+        let animation = Animation::from(&value["animation"]);
+
+        let frames: String = (0..6)
+            .map(|t| animation.get_sprite(t, 0).pixels[0].ch)
+            .collect();
+        assert_eq!(frames, "aabbba");
+
+        let frames: String = (0..6)
+            .map(|t| animation.get_sprite(t, 2).pixels[0].ch)
+            .collect();
+        assert_eq!(frames, "bbbaab");
+    }
+
+    #[test]
+    fn test_sprite_from_markup() {
+        let sprite = Sprite::from_markup("a{196}b{#ffaf00:52}c{:1}{{{}d");
+
+        let chars: String = sprite
+            .pixels
+            .iter()
+            .map(|p| p.ch)
+            .collect();
+        let colors: Vec<Color> = sprite
+            .pixels
+            .iter()
+            .map(|p| p.color)
+            .collect();
+
+        assert_eq!(chars, "abc{d");
+        assert_eq!(sprite.size, ScreenPoint::new(5, 1));
+        assert_eq!(
+            colors,
+            vec![
+                Color::none(),
+                Color {
+                    bg_color: None,
+                    fg_color: Some(ColorValue::Indexed(196)),
+                },
+                Color {
+                    bg_color: Some(ColorValue::Indexed(52)),
+                    fg_color: Some(ColorValue::Rgb(255, 175, 0)),
+                },
+                Color {
+                    bg_color: Some(ColorValue::Indexed(1)),
+                    fg_color: None,
+                },
+                Color::none(),
+            ]
+        );
+    }
+}
@@ -1,46 +1,81 @@
+use std::collections::HashMap;
 use std::collections::HashSet;
 use std::rc::Rc;
 
+use rand::random;
+
+use self::flow_field::FlowField;
+use self::light_map::cast_light;
 use self::map::Map;
-use self::map::TilePos;
+use self::message_log::Message;
+use self::message_log::MessageCategory;
+use self::message_log::MessageLog;
+use self::message_log::Severity;
+use self::npc::Faction;
 use self::npc::Npc;
 use self::npc::NpcAnimationId;
 use self::npc::NpcClass;
+use self::occupancy::Occupancy;
+use self::path::PathError;
+use self::path_queue::PathQueue;
+use self::path_queue::PATH_QUEUE_BUDGET;
 use self::selection::Selection;
+use self::task::goto_flow::GotoFlowTask;
 use self::task::idle::IdleCursorTask;
 use self::task::Task;
+use self::visibility::Visibility;
 use crate::common::MapPoint;
-use crate::common::ScreenPoint;
-use crate::common::TILE_SIZE;
+use crate::movement_profile::MovementProfile;
 use crate::npc_config::BaseNpc;
 use crate::npc_config::NpcConfig;
 use crate::renderer::draw_debug_info::DEBUG_INFO_PAGE_TOTAL;
-use crate::screen::Sprite;
 use crate::tile_config::BaseTile;
 use crate::tile_config::TileConfig;
+use crate::tile_config::TileId;
 
+pub mod chunk_graph;
 mod flood_fill;
+pub mod flow_field;
+pub mod light_map;
 mod map;
+pub mod message_log;
 pub mod npc;
+pub mod occupancy;
+pub mod path;
+pub mod path_queue;
+pub mod region;
 pub mod selection;
 pub mod task;
+pub mod visibility;
 
 pub struct State {
     pub astar_start: MapPoint,
     pub astar_goal: MapPoint,
-    pub astar_path: Vec<MapPoint>,
+    pub astar_path: Result<Vec<MapPoint>, PathError>,
 
     pub selection: Selection,
     pub dig_selection: HashSet<MapPoint>,
 
     pub debug_info_page: i32,
+    /// Hides what no friendly npc has seen, can be switched off for debugging.
+    pub fog_of_war: bool,
+    pub message_log: MessageLog,
 
     pub cursor_pos: MapPoint,
     pub elapsed_time: u64,
     pub map: Rc<Map>,
     pub map_pos: MapPoint,
+    /// Counts the changes of the map, so tasks notice when what they computed is outdated.
+    pub map_version: u64,
 
     pub npcs: Vec<Npc>,
+    /// The light levels cast by the torches of npcs, recomputed as they move.
+    pub npc_light: HashMap<MapPoint, i32>,
+    pub occupancy: Occupancy,
+
+    /// Flow fields of group orders by goal and movement profile, dropped when the map changes.
+    pub flow_fields: HashMap<(MapPoint, MovementProfile), Rc<FlowField>>,
+    pub path_queue: PathQueue,
 
     pub cursor_tasks: Vec<Box<dyn Task>>,
     pub soldier_tasks: Vec<Box<dyn Task>>,
@@ -59,50 +94,78 @@ impl State {
         let tile_config = Rc::new(TileConfig::from_file("tile_config.toml"));
         let npc_config = Rc::new(NpcConfig::from_file("npc_config.toml"));
         let elapsed_time = 0;
-        let map = Rc::new(Map::from_file("example_map.toml", &tile_config));
+        let mut map = Map::from_file("example_map.toml", &tile_config);
+        map.add_movement_profile(&MovementProfile::default(), &tile_config);
+        for base_npc in npc_config.iter() {
+            map.add_movement_profile(&base_npc.movement_profile, &tile_config);
+        }
+        let map = Rc::new(map);
         let map_pos = MapPoint::new(24, 1);
 
         let npcs = vec![
             Npc {
                 animation: NpcAnimationId::Idle,
+                animation_phase: random::<u16>() as u64,
+                index: 0,
                 npc_id: String::from("follower"),
                 pos: MapPoint::new(12, 10),
                 task: Box::new(IdleCursorTask {}),
             },
             Npc {
                 animation: NpcAnimationId::Idle,
+                animation_phase: random::<u16>() as u64,
+                index: 1,
                 npc_id: String::from("follower"),
                 pos: MapPoint::new(10, 10),
                 task: Box::new(IdleCursorTask {}),
             },
             Npc {
                 animation: NpcAnimationId::Idle,
+                animation_phase: random::<u16>() as u64,
+                index: 2,
                 npc_id: String::from("imp"),
                 pos: MapPoint::new(19, 11),
                 task: Box::new(IdleCursorTask {}),
             },
+            Npc {
+                animation: NpcAnimationId::Idle,
+                animation_phase: random::<u16>() as u64,
+                index: 3,
+                npc_id: String::from("ghost"),
+                pos: MapPoint::new(3, 3),
+                task: Box::new(IdleCursorTask {}),
+            },
         ];
+        let occupancy = Occupancy::new(&npcs);
 
         let cursor_tasks = Vec::new();
         let soldier_tasks = Vec::new();
         let worker_tasks = Vec::new();
 
-        Self {
+        let mut state = Self {
             astar_start: MapPoint::new(0, 0),
             astar_goal: MapPoint::new(0, 0),
-            astar_path: Vec::new(),
+            astar_path: Ok(Vec::new()),
 
             selection: Selection::new(),
             dig_selection: HashSet::new(),
 
             debug_info_page: 1,
+            fog_of_war: true,
+            message_log: MessageLog::default(),
 
             cursor_pos: MapPoint::new(0, 0),
             elapsed_time,
             map,
             map_pos,
+            map_version: 0,
 
             npcs,
+            npc_light: HashMap::new(),
+            occupancy,
+
+            flow_fields: HashMap::new(),
+            path_queue: PathQueue::default(),
 
             cursor_tasks,
             soldier_tasks,
@@ -112,37 +175,12 @@ impl State {
             tile_config,
 
             screen_size: MapPoint::new(0, 0),
-        }
-    }
+        };
 
-    // TODO: shouldn't this be done in the renderer?
-    pub fn get_map_sprite(&self) -> Sprite {
-        let mut pixels = Vec::new();
-        let width = TILE_SIZE.width() * self.map.size.width();
-        let height = self.map.size.height();
+        state.update_visibility();
+        state.update_npc_light();
 
-        for tile in &self.map.tiles {
-            let tile_id = tile.tile_id;
-            let animation_index = tile.animation_index;
-
-            let animation = &self
-                .tile_config
-                .get(tile_id)
-                .animations[animation_index];
-
-            let frame = (self.elapsed_time % animation.sprites.len() as u64) as usize;
-
-            let sprite = &animation.sprites[frame];
-
-            for pixel in &sprite.pixels {
-                pixels.push(pixel.clone());
-            }
-        }
-
-        Sprite {
-            pixels,
-            size: ScreenPoint::new(width, height),
-        }
+        state
     }
 
     // TODO: is it possible to prevent npc cloning here?
@@ -152,40 +190,117 @@ impl State {
         for i in 0..self.npcs.len() {
             let mut npc_clone = self.npcs[i].clone();
 
-            match self
+            let tasks = match self
                 .npc_config
                 .get(&npc_clone.npc_id)
                 .npc_class
             {
-                NpcClass::Debug => {
-                    State::assign_appropriate_task(&mut npc_clone, &mut self.cursor_tasks)
-                }
-                NpcClass::Soldier => {
-                    State::assign_appropriate_task(&mut npc_clone, &mut self.soldier_tasks)
-                }
-                NpcClass::Worker => {
-                    State::assign_appropriate_task(&mut npc_clone, &mut self.worker_tasks)
-                }
+                NpcClass::Debug => &mut self.cursor_tasks,
+                NpcClass::Soldier => &mut self.soldier_tasks,
+                NpcClass::Worker => &mut self.worker_tasks,
+            };
+
+            if let Some(task) = State::take_appropriate_task(&npc_clone, tasks) {
+                npc_clone.assign(task, self);
             }
 
             npc_clone.execute_next_action(self);
 
             self.npcs[i] = npc_clone;
         }
+
+        self.path_queue
+            .solve(&self.map, &self.tile_config, PATH_QUEUE_BUDGET);
+
+        self.update_visibility();
+        self.update_npc_light();
+    }
+
+    pub fn update_npc_light(&mut self) {
+        self.npc_light.clear();
+
+        for npc in &self.npcs {
+            let emission = self.get_base_npc(npc).light;
+            if emission <= 0 {
+                continue;
+            }
+
+            for (point, level) in cast_light(&npc.pos, emission, &self.map, &self.tile_config) {
+                let npc_level = self.npc_light.entry(point).or_insert(0);
+                *npc_level = (*npc_level).max(level);
+            }
+        }
+    }
+
+    /// The light level at `point`, of light emitting tiles or torches.
+    pub fn get_light_level(&self, point: &MapPoint) -> i32 {
+        let npc_level = self
+            .npc_light
+            .get(point)
+            .copied()
+            .unwrap_or(0);
+
+        self.map
+            .light_map
+            .get_level(point)
+            .max(npc_level)
+    }
+
+    /// Recomputes what the npcs of the player see.
+    pub fn update_visibility(&mut self) {
+        let viewers: Vec<(MapPoint, i32)> = self
+            .npcs
+            .iter()
+            .map(|n| (n, self.get_base_npc(n)))
+            .filter(|(_, b)| b.faction == Faction::Player)
+            .map(|(n, b)| (n.pos.clone(), b.vision))
+            .collect();
+
+        Rc::make_mut(&mut self.map).update_visibility(&viewers, &self.tile_config);
+    }
+
+    pub fn get_visibility(&self, point: &MapPoint) -> Visibility {
+        if !self.fog_of_war {
+            return Visibility::Visible;
+        }
+
+        self.map.get_visibility(point)
+    }
+
+    /// Npcs of the player are always shown, the others only while in sight.
+    pub fn is_npc_visible(&self, npc: &Npc) -> bool {
+        self.get_base_npc(npc).faction == Faction::Player
+            || self.get_visibility(&npc.pos) == Visibility::Visible
+    }
+
+    pub fn toggle_fog_of_war(&mut self) {
+        self.fog_of_war = !self.fog_of_war;
     }
 
-    fn assign_appropriate_task(npc: &mut Npc, tasks: &mut Vec<Box<dyn Task>>) {
+    fn take_appropriate_task(npc: &Npc, tasks: &mut Vec<Box<dyn Task>>) -> Option<Box<dyn Task>> {
         for i in 0..tasks.len() {
             if tasks[i].get_priority() > npc.task.get_priority() {
-                let task = tasks.swap_remove(i);
-                npc.assign(task);
-                return;
+                return Some(tasks.swap_remove(i));
             }
         }
+
+        None
     }
 
+    /// Sets the size of the map viewport in tiles, keeping the cursor inside.
     pub fn resize(&mut self, screen_size: &MapPoint) {
         self.screen_size = screen_size.clone();
+
+        self.cursor_pos.x = self
+            .cursor_pos
+            .x
+            .min(screen_size.width() - 1)
+            .max(0);
+        self.cursor_pos.y = self
+            .cursor_pos
+            .y
+            .min(screen_size.height() - 1)
+            .max(0);
     }
 
     pub fn elapse_time(&mut self) {
@@ -208,10 +323,56 @@ impl State {
             .unwrap_or(false)
     }
 
-    pub fn is_tile_traversable(&self, point: &MapPoint) -> bool {
-        self.get_base_tile_at(point)
-            .map(|t| t.is_traversable())
-            .unwrap_or(false)
+    pub fn is_tile_traversable(&self, point: &MapPoint, profile: &MovementProfile) -> bool {
+        self.map
+            .is_traversable(point, &self.tile_config, profile)
+    }
+
+    pub fn set_tile(&mut self, point: &MapPoint, tile_id: TileId) {
+        Rc::make_mut(&mut self.map).set_tile(point, tile_id, &self.tile_config);
+        self.map_version += 1;
+        self.flow_fields.clear();
+        self.update_visibility();
+        self.update_npc_light();
+    }
+
+    pub fn get_flow_field(&mut self, goal: &MapPoint, profile: &MovementProfile) -> Rc<FlowField> {
+        let key = (goal.clone(), profile.clone());
+
+        if let Some(flow_field) = self.flow_fields.get(&key) {
+            return Rc::clone(flow_field);
+        }
+
+        let flow_field = Rc::new(FlowField::new(goal, &self.map, &self.tile_config, profile));
+
+        self.flow_fields
+            .insert(key, Rc::clone(&flow_field));
+
+        flow_field
+    }
+
+    /// Sends every cursor npc to `goal`. Npcs with the same movement profile
+    /// share a single flow field instead of searching a path each.
+    pub fn order_rally(&mut self, goal: MapPoint) {
+        let npc_total = self
+            .npcs
+            .iter()
+            .filter(|n| matches!(self.get_base_npc(n).npc_class, NpcClass::Debug))
+            .count();
+
+        for _ in 0..npc_total {
+            self.cursor_tasks
+                .push(Box::new(GotoFlowTask::new(goal.clone())));
+        }
+    }
+
+    pub fn post_message(&mut self, category: MessageCategory, severity: Severity, text: String) {
+        self.message_log.post(Message {
+            time: self.elapsed_time,
+            category,
+            severity,
+            text,
+        });
     }
 
     pub fn debug_info_next_page(&mut self) {
@@ -309,47 +470,33 @@ impl State {
             size: Some(size),
         } = &self.selection
         {
+            let mut designated = 0;
+
             for y in pos.y..(pos.y + size.y) {
                 for x in pos.x..(pos.x + size.x) {
-                    if self.is_tile_minable(&MapPoint::new(x, y)) {
-                        self.dig_selection
-                            .insert(MapPoint::new(x, y));
+                    if self.is_tile_minable(&MapPoint::new(x, y))
+                        && self
+                            .dig_selection
+                            .insert(MapPoint::new(x, y))
+                    {
+                        designated += 1;
                     }
                 }
             }
+
+            if designated == 0 {
+                self.post_message(
+                    MessageCategory::Job,
+                    Severity::Warning,
+                    String::from("Nothing to dig in the selection"),
+                );
+            } else {
+                self.post_message(
+                    MessageCategory::Job,
+                    Severity::Info,
+                    format!("{designated} tiles designated for digging"),
+                );
+            }
         }
     }
 }
-
-pub fn get_shortest_path(
-    start: &MapPoint,
-    goal: &MapPoint,
-    map: &Map,
-    tile_config: &TileConfig,
-) -> Vec<MapPoint> {
-    let path = pathfinding::prelude::astar(
-        start,
-        |p| successors(p, map, tile_config),
-        |p| heuristic(p, goal),
-        |p| p == goal,
-    );
-
-    path.map_or(Vec::new(), |p| p.0)
-}
-
-fn successors(point: &MapPoint, map: &Map, tile_config: &TileConfig) -> Vec<(MapPoint, u32)> {
-    let neigh_tiles: Vec<TilePos> = map
-        .get_neighborhood4(point)
-        .filter_traversable(tile_config)
-        .into();
-
-    neigh_tiles
-        .iter()
-        .map(|t| (t.pos.clone(), 1))
-        .collect()
-}
-
-fn heuristic(point: &MapPoint, goal: &MapPoint) -> u32 {
-    (pathfinding::prelude::absdiff(point.x, goal.x)
-        + pathfinding::prelude::absdiff(point.y, goal.y)) as u32
-}
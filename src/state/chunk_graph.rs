@@ -0,0 +1,569 @@
+use std::collections::HashMap;
+
+use super::map::Map;
+use super::path::get_step_cost;
+use super::path::get_traversable_neighbors;
+use super::path::heuristic;
+use crate::common::MapPoint;
+use crate::movement_profile::MovementProfile;
+use crate::movement_profile::Traversability;
+use crate::tile_config::TileConfig;
+
+/// Width and height of a chunk in tiles.
+pub const CHUNK_SIZE: i32 = 16;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum Border {
+    Right,
+    Down,
+}
+
+/// Path costs from each entrance to the other entrances of its chunk.
+type IntraEdges = HashMap<MapPoint, Vec<(MapPoint, u32)>>;
+
+/// Abstract graph over square chunks of the map, used to find long paths
+/// without searching every tile in between. Its nodes are the entrances between
+/// neighboring chunks, connected by cached path costs inside each chunk.
+#[derive(Clone, Debug, Default)]
+pub struct ChunkGraph {
+    /// Pairs of tiles facing each other across a border, keyed by chunk
+    /// coordinates and the border to the right or lower neighbor chunk. They
+    /// only depend on traversability and are shared by all profiles.
+    entrances: HashMap<(MapPoint, Border), Vec<(MapPoint, MapPoint)>>,
+    /// The intra chunk path costs of each movement profile.
+    intra_edges: HashMap<MovementProfile, IntraEdges>,
+}
+
+impl ChunkGraph {
+    /// Finds the entrances of every chunk, path costs are added per profile
+    /// with `add_profile`.
+    pub fn new(map: &Map, tile_config: &TileConfig, traversability: &Traversability) -> Self {
+        let mut chunk_graph = Self::default();
+        let chunks = get_chunk(&(&map.size - &MapPoint::new(1, 1)));
+
+        for y in 0..=chunks.y {
+            for x in 0..=chunks.x {
+                let chunk = MapPoint::new(x, y);
+                chunk_graph.build_entrances(
+                    &chunk,
+                    Border::Right,
+                    map,
+                    tile_config,
+                    traversability,
+                );
+                chunk_graph.build_entrances(&chunk, Border::Down, map, tile_config, traversability);
+            }
+        }
+
+        chunk_graph
+    }
+
+    /// Caches the path costs between the entrances of every chunk for `profile`,
+    /// which has to share the traversability of the graph.
+    pub fn add_profile(&mut self, profile: &MovementProfile, map: &Map, tile_config: &TileConfig) {
+        if self.intra_edges.contains_key(profile) {
+            return;
+        }
+
+        self.intra_edges
+            .insert(profile.clone(), HashMap::new());
+
+        let chunks = get_chunk(&(&map.size - &MapPoint::new(1, 1)));
+
+        for y in 0..=chunks.y {
+            for x in 0..=chunks.x {
+                self.build_intra_edges(&MapPoint::new(x, y), map, tile_config, profile);
+            }
+        }
+    }
+
+    /// Rebuilds the chunk of `point` after its tile has changed, and the
+    /// neighbor chunks if `point` lies on a border.
+    pub fn update(
+        &mut self,
+        point: &MapPoint,
+        map: &Map,
+        tile_config: &TileConfig,
+        traversability: &Traversability,
+    ) {
+        let chunk = get_chunk(point);
+        let local = point - &MapPoint::new(chunk.x * CHUNK_SIZE, chunk.y * CHUNK_SIZE);
+
+        let mut borders = Vec::new();
+        if local.x == 0 {
+            borders.push((chunk.left(), Border::Right));
+        }
+        if local.x == CHUNK_SIZE - 1 {
+            borders.push((chunk.clone(), Border::Right));
+        }
+        if local.y == 0 {
+            borders.push((chunk.up(), Border::Down));
+        }
+        if local.y == CHUNK_SIZE - 1 {
+            borders.push((chunk.clone(), Border::Down));
+        }
+
+        let mut chunks = vec![chunk];
+        for (border_chunk, border) in &borders {
+            match border {
+                Border::Right => chunks.push(border_chunk.right()),
+                Border::Down => chunks.push(border_chunk.down()),
+            }
+            chunks.push(border_chunk.clone());
+        }
+        chunks.sort();
+        chunks.dedup();
+
+        for chunk in &chunks {
+            for node in self.get_nodes(chunk) {
+                for intra_edges in self.intra_edges.values_mut() {
+                    intra_edges.remove(&node);
+                }
+            }
+        }
+
+        for (border_chunk, border) in borders {
+            self.build_entrances(&border_chunk, border, map, tile_config, traversability);
+        }
+
+        let profiles: Vec<MovementProfile> = self
+            .intra_edges
+            .keys()
+            .cloned()
+            .collect();
+        for profile in &profiles {
+            for chunk in &chunks {
+                self.build_intra_edges(chunk, map, tile_config, profile);
+            }
+        }
+    }
+
+    /// Finds a path by searching the abstract graph first and then refining each
+    /// abstract edge inside its chunk. The result is close to, but not always, the
+    /// shortest path.
+    pub fn get_path(
+        &self,
+        start: &MapPoint,
+        goal: &MapPoint,
+        map: &Map,
+        tile_config: &TileConfig,
+        profile: &MovementProfile,
+    ) -> Option<Vec<MapPoint>> {
+        let intra_edges = self.intra_edges.get(profile)?;
+        let start_chunk = get_chunk(start);
+        let goal_chunk = get_chunk(goal);
+
+        let start_costs = get_chunk_costs(start, &start_chunk, false, map, tile_config, profile);
+        let goal_costs = get_chunk_costs(goal, &goal_chunk, true, map, tile_config, profile);
+
+        let mut start_edges: Vec<(MapPoint, u32)> = self
+            .get_nodes(&start_chunk)
+            .into_iter()
+            .filter_map(|n| start_costs.get(&n).map(|c| (n, *c)))
+            .collect();
+
+        if let Some(cost) = start_costs.get(goal) {
+            start_edges.push((goal.clone(), *cost));
+        }
+
+        let min_move_cost = profile.min_move_cost(tile_config);
+
+        let abstract_path = pathfinding::prelude::astar(
+            start,
+            |node| {
+                let mut successors = if node == start {
+                    start_edges.clone()
+                } else {
+                    intra_edges
+                        .get(node)
+                        .cloned()
+                        .unwrap_or_default()
+                };
+
+                for partner in self.get_partners(node) {
+                    let tile_pos = map.get_tile_pos(&partner).unwrap();
+                    let cost = get_step_cost(node, &tile_pos, tile_config, profile);
+                    successors.push((partner, cost));
+                }
+
+                if get_chunk(node) == goal_chunk {
+                    if let Some(cost) = goal_costs.get(node) {
+                        successors.push((goal.clone(), *cost));
+                    }
+                }
+
+                successors
+            },
+            |node| heuristic(node, goal, min_move_cost, profile.diagonal),
+            |node| node == goal,
+        )?
+        .0;
+
+        let mut steps = vec![start.clone()];
+
+        for edge in abstract_path.windows(2) {
+            let chunk = get_chunk(&edge[0]);
+
+            if chunk != get_chunk(&edge[1]) {
+                steps.push(edge[1].clone());
+                continue;
+            }
+
+            let segment = get_chunk_path(&edge[0], &edge[1], &chunk, map, tile_config, profile)?;
+            steps.extend(segment.into_iter().skip(1));
+        }
+
+        Some(steps)
+    }
+
+    fn build_entrances(
+        &mut self,
+        chunk: &MapPoint,
+        border: Border,
+        map: &Map,
+        tile_config: &TileConfig,
+        traversability: &Traversability,
+    ) {
+        let origin = MapPoint::new(chunk.x * CHUNK_SIZE, chunk.y * CHUNK_SIZE);
+
+        // the first tile on the near side of the border, the direction along
+        // the border and the offset to the far side
+        let (first, along, across) = match border {
+            Border::Right => (
+                &origin + &MapPoint::new(CHUNK_SIZE - 1, 0),
+                MapPoint::new(0, 1),
+                MapPoint::new(1, 0),
+            ),
+            Border::Down => (
+                &origin + &MapPoint::new(0, CHUNK_SIZE - 1),
+                MapPoint::new(1, 0),
+                MapPoint::new(0, 1),
+            ),
+        };
+
+        let mut entrances = Vec::new();
+        let mut run = Vec::new();
+
+        for i in 0..=CHUNK_SIZE {
+            let near = &first + &MapPoint::new(along.x * i, along.y * i);
+            let far = &near + &across;
+
+            if i < CHUNK_SIZE
+                && map.is_passable(&near, tile_config, traversability)
+                && map.is_passable(&far, tile_config, traversability)
+            {
+                run.push(near);
+                continue;
+            }
+
+            // every run of open tiles along the border gets one entrance in its middle
+            if !run.is_empty() {
+                let middle: MapPoint = run[run.len() / 2].clone();
+                let partner = &middle + &across;
+                entrances.push((middle, partner));
+                run.clear();
+            }
+        }
+
+        if entrances.is_empty() {
+            self.entrances
+                .remove(&(chunk.clone(), border));
+        } else {
+            self.entrances
+                .insert((chunk.clone(), border), entrances);
+        }
+    }
+
+    fn build_intra_edges(
+        &mut self,
+        chunk: &MapPoint,
+        map: &Map,
+        tile_config: &TileConfig,
+        profile: &MovementProfile,
+    ) {
+        let nodes = self.get_nodes(chunk);
+        let intra_edges = self
+            .intra_edges
+            .entry(profile.clone())
+            .or_default();
+
+        for node in &nodes {
+            let costs = get_chunk_costs(node, chunk, false, map, tile_config, profile);
+
+            let edges = nodes
+                .iter()
+                .filter_map(|n| costs.get(n).map(|c| (n.clone(), *c)))
+                .collect();
+
+            intra_edges.insert(node.clone(), edges);
+        }
+    }
+
+    fn get_nodes(&self, chunk: &MapPoint) -> Vec<MapPoint> {
+        let empty = Vec::new();
+        let get = |chunk: MapPoint, border| {
+            self.entrances
+                .get(&(chunk, border))
+                .unwrap_or(&empty)
+        };
+
+        let mut nodes: Vec<MapPoint> = get(chunk.clone(), Border::Right)
+            .iter()
+            .chain(get(chunk.clone(), Border::Down))
+            .map(|e| e.0.clone())
+            .chain(
+                get(chunk.left(), Border::Right)
+                    .iter()
+                    .chain(get(chunk.up(), Border::Down))
+                    .map(|e| e.1.clone()),
+            )
+            .collect();
+
+        nodes.sort();
+        nodes.dedup();
+
+        nodes
+    }
+
+    /// The entrances in neighboring chunks directly connected to `node`.
+    fn get_partners(&self, node: &MapPoint) -> Vec<MapPoint> {
+        let chunk = get_chunk(node);
+        let mut partners = Vec::new();
+
+        for key in [
+            (chunk.clone(), Border::Right),
+            (chunk.clone(), Border::Down),
+        ] {
+            for (near, far) in self
+                .entrances
+                .get(&key)
+                .into_iter()
+                .flatten()
+            {
+                if near == node {
+                    partners.push(far.clone());
+                }
+            }
+        }
+
+        for key in [(chunk.left(), Border::Right), (chunk.up(), Border::Down)] {
+            for (near, far) in self
+                .entrances
+                .get(&key)
+                .into_iter()
+                .flatten()
+            {
+                if far == node {
+                    partners.push(near.clone());
+                }
+            }
+        }
+
+        partners
+    }
+}
+
+fn get_chunk(point: &MapPoint) -> MapPoint {
+    MapPoint::new(
+        point.x.div_euclid(CHUNK_SIZE),
+        point.y.div_euclid(CHUNK_SIZE),
+    )
+}
+
+fn get_chunk_successors(
+    point: &MapPoint,
+    chunk: &MapPoint,
+    map: &Map,
+    tile_config: &TileConfig,
+    profile: &MovementProfile,
+) -> Vec<(MapPoint, u32)> {
+    get_traversable_neighbors(point, map, tile_config, profile)
+        .into_iter()
+        .filter(|t| &get_chunk(&t.pos) == chunk)
+        .map(|t| {
+            let cost = get_step_cost(point, &t, tile_config, profile);
+            (t.pos, cost)
+        })
+        .collect()
+}
+
+/// Costs of the cheapest paths inside `chunk` from `point` to every tile of the
+/// chunk, or from every tile to `point` if `reverse` is set.
+fn get_chunk_costs(
+    point: &MapPoint,
+    chunk: &MapPoint,
+    reverse: bool,
+    map: &Map,
+    tile_config: &TileConfig,
+    profile: &MovementProfile,
+) -> HashMap<MapPoint, u32> {
+    let costs = pathfinding::prelude::dijkstra_all(point, |p| {
+        if !reverse {
+            return get_chunk_successors(p, chunk, map, tile_config, profile);
+        }
+
+        // stepping backwards from `p` costs as much as stepping onto `p`
+        let tile_pos = map.get_tile_pos(p).unwrap();
+        get_chunk_successors(p, chunk, map, tile_config, profile)
+            .into_iter()
+            .map(|(n, _)| {
+                let cost = get_step_cost(&n, &tile_pos, tile_config, profile);
+                (n, cost)
+            })
+            .collect()
+    });
+
+    costs
+        .into_iter()
+        .map(|(p, (_, cost))| (p, cost))
+        .collect()
+}
+
+fn get_chunk_path(
+    start: &MapPoint,
+    goal: &MapPoint,
+    chunk: &MapPoint,
+    map: &Map,
+    tile_config: &TileConfig,
+    profile: &MovementProfile,
+) -> Option<Vec<MapPoint>> {
+    let min_move_cost = profile.min_move_cost(tile_config);
+
+    pathfinding::prelude::astar(
+        start,
+        |p| get_chunk_successors(p, chunk, map, tile_config, profile),
+        |p| heuristic(p, goal, min_move_cost, profile.diagonal),
+        |p| p == goal,
+    )
+    .map(|p| p.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::path::get_shortest_path;
+    use crate::state::State;
+
+    const DIRT_FLOOR: [char; 3] = ['[', '_', ']'];
+    const DIRT_WALL: [char; 3] = ['[', ':', ']'];
+
+    /// A 64 x 64 hall with a wall across its middle, open only at its top end.
+    fn get_large_map(state: &State) -> Map {
+        let mut tile_ids = String::new();
+        for y in 0..64 {
+            for x in 0..64 {
+                if x == 32 && y > 4 {
+                    tile_ids.push_str("[:]");
+                } else {
+                    tile_ids.push_str("[_]");
+                }
+            }
+            tile_ids.push('\n');
+        }
+
+        let mut map = Map::from_tile_ids(&tile_ids, &state.tile_config);
+        map.add_movement_profile(&MovementProfile::default(), &state.tile_config);
+
+        map
+    }
+
+    fn assert_valid_path(
+        path: &[MapPoint],
+        start: &MapPoint,
+        goal: &MapPoint,
+        state: &State,
+        map: &Map,
+    ) {
+        assert_eq!(path.first(), Some(start));
+        assert_eq!(path.last(), Some(goal));
+
+        for step in path.windows(2) {
+            let distance = (step[0].x - step[1].x).abs() + (step[0].y - step[1].y).abs();
+            assert_eq!(distance, 1);
+            assert!(map.is_traversable(&step[1], &state.tile_config, &MovementProfile::default()));
+        }
+    }
+
+    #[test]
+    fn test_chunk_graph_path() {
+        let state = State::new();
+        let map = get_large_map(&state);
+        let profile = MovementProfile::default();
+        let chunk_graph = map.get_chunk_graph(&profile).unwrap();
+
+        let start = MapPoint::new(20, 60);
+        let goal = MapPoint::new(44, 60);
+
+        let path = chunk_graph
+            .get_path(&start, &goal, &map, &state.tile_config, &profile)
+            .unwrap();
+        let shortest_path =
+            get_shortest_path(&start, &goal, &map, &state.tile_config, &profile).unwrap();
+
+        assert_valid_path(&path, &start, &goal, &state, &map);
+        assert!(path.len() >= shortest_path.len());
+        assert!(path.len() <= shortest_path.len() * 12 / 10);
+    }
+
+    #[test]
+    fn test_chunk_graph_update() {
+        let state = State::new();
+        let mut map = get_large_map(&state);
+        let profile = MovementProfile::default();
+
+        // closes the top end of the wall, so the only way around is gone ...
+        for y in 0..5 {
+            map.set_tile(&MapPoint::new(32, y), DIRT_WALL, &state.tile_config);
+        }
+
+        let start = MapPoint::new(20, 60);
+        let goal = MapPoint::new(44, 60);
+
+        let path = map
+            .get_chunk_graph(&profile)
+            .unwrap()
+            .get_path(&start, &goal, &map, &state.tile_config, &profile);
+        assert_eq!(path, None);
+
+        // ... and opens a gap at its bottom end
+        map.set_tile(&MapPoint::new(32, 62), DIRT_FLOOR, &state.tile_config);
+
+        let path = map
+            .get_chunk_graph(&profile)
+            .unwrap()
+            .get_path(&start, &goal, &map, &state.tile_config, &profile)
+            .unwrap();
+        assert_valid_path(&path, &start, &goal, &state, &map);
+        assert!(path.contains(&MapPoint::new(32, 62)));
+        assert!(path.len() < 30);
+    }
+
+    #[test]
+    fn test_chunk_graph_shared_by_traversability() {
+        let state = State::new();
+        let mut map = get_large_map(&state);
+        let profile = MovementProfile {
+            diagonal: true,
+            ..MovementProfile::default()
+        };
+
+        map.add_movement_profile(&profile, &state.tile_config);
+        assert_eq!(map.chunk_graphs.len(), 1);
+
+        // the shared entrances follow map changes for every profile
+        map.set_tile(&MapPoint::new(32, 62), DIRT_FLOOR, &state.tile_config);
+
+        let start = MapPoint::new(20, 60);
+        let goal = MapPoint::new(44, 60);
+
+        let path = map
+            .get_chunk_graph(&profile)
+            .unwrap()
+            .get_path(&start, &goal, &map, &state.tile_config, &profile)
+            .unwrap();
+
+        assert_eq!(path.first(), Some(&start));
+        assert_eq!(path.last(), Some(&goal));
+        assert!(path.len() < 40);
+    }
+}
@@ -2,31 +2,37 @@ use std::collections::HashSet;
 
 use super::State;
 use crate::common::MapPoint;
+use crate::movement_profile::MovementProfile;
 
 impl State {
-    pub fn flood_fill_map(&self, start: MapPoint) -> HashSet<MapPoint> {
-        self.flood_fill_map_recursive(start, HashSet::new())
+    pub fn flood_fill_map(&self, start: MapPoint, profile: &MovementProfile) -> HashSet<MapPoint> {
+        flood_fill(start, |p| self.is_tile_traversable(p, profile))
     }
+}
 
-    #[allow(clippy::let_and_return)]
-    fn flood_fill_map_recursive(
-        &self,
-        start: MapPoint,
-        mut fill: HashSet<MapPoint>,
-    ) -> HashSet<MapPoint> {
-        if fill.contains(&start) || !self.is_tile_traversable(&start) {
-            return fill;
+/// Collects all points 4-connected to `start` for which `is_fillable` holds.
+/// Uses an explicit stack, so the size of the fill is not limited by the call stack.
+pub fn flood_fill<F>(start: MapPoint, is_fillable: F) -> HashSet<MapPoint>
+where
+    F: Fn(&MapPoint) -> bool,
+{
+    let mut fill = HashSet::new();
+    let mut stack = vec![start];
+
+    while let Some(point) = stack.pop() {
+        if fill.contains(&point) || !is_fillable(&point) {
+            continue;
         }
 
-        fill.insert(start.clone());
-
-        let fill = self.flood_fill_map_recursive(start.left(), fill);
-        let fill = self.flood_fill_map_recursive(start.right(), fill);
-        let fill = self.flood_fill_map_recursive(start.up(), fill);
-        let fill = self.flood_fill_map_recursive(start.down(), fill);
+        stack.push(point.left());
+        stack.push(point.right());
+        stack.push(point.up());
+        stack.push(point.down());
 
-        fill
+        fill.insert(point);
     }
+
+    fill
 }
 
 #[cfg(test)]
@@ -37,10 +43,10 @@ mod tests {
     fn test_flood_fill_map_corner() {
         let state = State::new();
 
-        let x = state.flood_fill_map(MapPoint::new(0, 0));
+        let x = state.flood_fill_map(MapPoint::new(0, 0), &MovementProfile::default());
         assert_eq!(x.len(), 0);
 
-        let x = state.flood_fill_map(MapPoint::new(9, 0));
+        let x = state.flood_fill_map(MapPoint::new(9, 0), &MovementProfile::default());
         assert_eq!(x.len(), 0);
     }
 
@@ -48,10 +54,10 @@ mod tests {
     fn test_flood_fill_map_hall_left() {
         let state = State::new();
 
-        let x = state.flood_fill_map(MapPoint::new(2, 2));
+        let x = state.flood_fill_map(MapPoint::new(2, 2), &MovementProfile::default());
         assert_eq!(x.len(), 52);
 
-        let x = state.flood_fill_map(MapPoint::new(3, 3));
+        let x = state.flood_fill_map(MapPoint::new(3, 3), &MovementProfile::default());
         assert_eq!(x.len(), 52);
     }
 
@@ -59,10 +65,31 @@ mod tests {
     fn test_flood_fill_map_hall_right() {
         let state = State::new();
 
-        let x = state.flood_fill_map(MapPoint::new(18, 7));
+        let x = state.flood_fill_map(MapPoint::new(18, 7), &MovementProfile::default());
         assert_eq!(x.len(), 50);
 
-        let x = state.flood_fill_map(MapPoint::new(19, 7));
+        let x = state.flood_fill_map(MapPoint::new(19, 7), &MovementProfile::default());
         assert_eq!(x.len(), 50);
     }
+
+    #[test]
+    fn test_flood_fill_large_area() {
+        let x = flood_fill(MapPoint::new(0, 0), |p| {
+            p.x >= 0 && p.x < 500 && p.y >= 0 && p.y < 500
+        });
+        assert_eq!(x.len(), 250_000);
+    }
+
+    #[test]
+    fn test_flood_fill_map_lava_walker() {
+        let state = State::new();
+        let imp_profile = &state
+            .npc_config
+            .get(&String::from("imp"))
+            .movement_profile;
+
+        let x = state.flood_fill_map(MapPoint::new(2, 2), imp_profile);
+        assert!(x.len() > 52);
+        assert!(x.contains(&MapPoint::new(5, 2)));
+    }
 }
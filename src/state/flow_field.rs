@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+
+use super::map::Map;
+use super::path::get_step_cost;
+use super::path::get_traversable_neighbors;
+use crate::common::MapPoint;
+use crate::movement_profile::MovementProfile;
+use crate::tile_config::TileConfig;
+
+/// Distances to a single goal for every tile which can reach it. Any number of
+/// npcs can walk to the goal by following the steps, without searching on their own.
+pub struct FlowField {
+    pub goal: MapPoint,
+
+    /// For every point: the next step towards the goal and the remaining cost.
+    steps: HashMap<MapPoint, (MapPoint, u32)>,
+}
+
+impl FlowField {
+    pub fn new(
+        goal: &MapPoint,
+        map: &Map,
+        tile_config: &TileConfig,
+        profile: &MovementProfile,
+    ) -> Self {
+        if !map.is_traversable(goal, tile_config, profile) {
+            return Self {
+                goal: goal.clone(),
+                steps: HashMap::new(),
+            };
+        }
+
+        // runs dijkstra backwards from the goal, so the parent of every point is
+        // its next step and each edge is weighted by the cost of stepping onto its parent
+        let steps = pathfinding::prelude::dijkstra_all(goal, |point| {
+            let tile_pos = map.get_tile_pos(point).unwrap();
+
+            get_traversable_neighbors(point, map, tile_config, profile)
+                .into_iter()
+                .map(|t| {
+                    let cost = get_step_cost(&t.pos, &tile_pos, tile_config, profile);
+                    (t.pos, cost)
+                })
+                .collect::<Vec<_>>()
+        });
+
+        Self {
+            goal: goal.clone(),
+            steps,
+        }
+    }
+
+    pub fn get_next_step(&self, point: &MapPoint) -> Option<MapPoint> {
+        self.steps
+            .get(point)
+            .map(|s| s.0.clone())
+    }
+
+    pub fn get_cost(&self, point: &MapPoint) -> Option<u32> {
+        if point == &self.goal {
+            return Some(0);
+        }
+
+        self.steps.get(point).map(|s| s.1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::State;
+
+    #[test]
+    fn test_flow_field_leads_to_goal() {
+        let state = State::new();
+        let goal = MapPoint::new(12, 13);
+        let flow_field = FlowField::new(
+            &goal,
+            &state.map,
+            &state.tile_config,
+            &MovementProfile::default(),
+        );
+
+        let mut point = MapPoint::new(9, 10);
+        let mut steps = 0;
+        while let Some(next_step) = flow_field.get_next_step(&point) {
+            point = next_step;
+            steps += 1;
+        }
+
+        assert_eq!(point, goal);
+        assert_eq!(steps, 6);
+        assert_eq!(flow_field.get_cost(&MapPoint::new(9, 10)), Some(60));
+    }
+
+    #[test]
+    fn test_flow_field_unreachable() {
+        let state = State::new();
+        let flow_field = FlowField::new(
+            &MapPoint::new(12, 13),
+            &state.map,
+            &state.tile_config,
+            &MovementProfile::default(),
+        );
+
+        assert_eq!(flow_field.get_next_step(&MapPoint::new(18, 7)), None);
+        assert_eq!(flow_field.get_cost(&MapPoint::new(18, 7)), None);
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+
+use super::map::Map;
+use super::visibility::get_field_of_view;
+use crate::common::MapPoint;
+use crate::tile_config::TileConfig;
+
+/// The light cast by a single source, the level of each lit point.
+pub type Light = Vec<(MapPoint, i32)>;
+
+/// The light levels of the map cast by light emitting tiles.
+#[derive(Clone, Default)]
+pub struct LightMap {
+    /// The level of light each source casts on a point, by source position.
+    contributions: Vec<HashMap<MapPoint, i32>>,
+    levels: Vec<i32>,
+    size: MapPoint,
+    /// The emission and the lit points of each source.
+    sources: HashMap<MapPoint, (i32, Vec<MapPoint>)>,
+}
+
+/// The light of a source with `emission` at `origin`. It fades by one level per
+/// tile and is stopped by solid blocks, which are lit themselves.
+pub fn cast_light(origin: &MapPoint, emission: i32, map: &Map, tile_config: &TileConfig) -> Light {
+    get_field_of_view(origin, emission, map, tile_config)
+        .into_iter()
+        .map(|p| {
+            let offset = &p - origin;
+            let distance = ((offset.x * offset.x + offset.y * offset.y) as f32)
+                .sqrt()
+                .round() as i32;
+            (p, emission - distance)
+        })
+        .filter(|(_, level)| *level > 0)
+        .collect()
+}
+
+impl LightMap {
+    pub fn new(map: &Map, tile_config: &TileConfig) -> Self {
+        let mut light_map = Self {
+            contributions: vec![HashMap::new(); map.tiles.len()],
+            levels: vec![0; map.tiles.len()],
+            size: map.size.clone(),
+            sources: HashMap::new(),
+        };
+
+        for y in 0..map.size.height() {
+            for x in 0..map.size.width() {
+                light_map.cast(&MapPoint::new(x, y), map, tile_config);
+            }
+        }
+
+        light_map
+    }
+
+    pub fn get_level(&self, point: &MapPoint) -> i32 {
+        if point.x < 0
+            || point.x >= self.size.width()
+            || point.y < 0
+            || point.y >= self.size.height()
+        {
+            return 0;
+        }
+
+        self.levels[self.get_index(point)]
+    }
+
+    /// Recasts the light of the tile at `point` and of the sources reaching it,
+    /// as the tile changed and may block or let through light differently.
+    /// Only the levels of the points lit before or after are recomputed.
+    pub fn update(&mut self, point: &MapPoint, map: &Map, tile_config: &TileConfig) {
+        let affected_sources: Vec<MapPoint> = self
+            .sources
+            .iter()
+            .filter(|(source, (emission, _))| {
+                let offset = point - source;
+                offset.x * offset.x + offset.y * offset.y <= emission * emission
+            })
+            .map(|(source, _)| source.clone())
+            .chain(std::iter::once(point.clone()))
+            .collect();
+
+        let mut affected_points = Vec::new();
+
+        for source in &affected_sources {
+            if let Some((_, lit_points)) = self.sources.remove(source) {
+                for p in &lit_points {
+                    let i = self.get_index(p);
+                    self.contributions[i].remove(source);
+                }
+                affected_points.extend(lit_points);
+            }
+        }
+
+        for source in &affected_sources {
+            self.cast(source, map, tile_config);
+        }
+
+        for p in affected_points {
+            let i = self.get_index(&p);
+            self.levels[i] = self.contributions[i]
+                .values()
+                .copied()
+                .max()
+                .unwrap_or(0);
+        }
+    }
+
+    fn get_index(&self, point: &MapPoint) -> usize {
+        (self.size.width() * point.y + point.x) as usize
+    }
+
+    /// Adds the light of the tile at `point`, if it emits any.
+    fn cast(&mut self, point: &MapPoint, map: &Map, tile_config: &TileConfig) {
+        let Some(tile) = map.get_tile(point) else {
+            return;
+        };
+
+        let emission = tile_config.get(tile.tile_id).light;
+        if emission <= 0 {
+            return;
+        }
+
+        let light = cast_light(point, emission, map, tile_config);
+        let mut lit_points = Vec::new();
+
+        for (p, level) in light {
+            let i = self.get_index(&p);
+            self.levels[i] = self.levels[i].max(level);
+            self.contributions[i].insert(point.clone(), level);
+            lit_points.push(p);
+        }
+
+        self.sources
+            .insert(point.clone(), (emission, lit_points));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::State;
+
+    const DIRT_FLOOR: [char; 3] = ['[', '_', ']'];
+    const LAVA_FLOOR: [char; 3] = ['[', 'v', ']'];
+
+    #[test]
+    fn test_light_map_update() {
+        let state = State::new();
+        let tile_ids = "[v][:][_][_]\n";
+        let mut map = Map::from_tile_ids(tile_ids, &state.tile_config);
+        let emission = state.tile_config.get(LAVA_FLOOR).light;
+
+        assert_eq!(
+            map.light_map
+                .get_level(&MapPoint::new(0, 0)),
+            emission
+        );
+        assert_eq!(
+            map.light_map
+                .get_level(&MapPoint::new(1, 0)),
+            emission - 1
+        );
+        assert_eq!(
+            map.light_map
+                .get_level(&MapPoint::new(2, 0)),
+            0
+        );
+
+        map.set_tile(&MapPoint::new(1, 0), DIRT_FLOOR, &state.tile_config);
+
+        assert_eq!(
+            map.light_map
+                .get_level(&MapPoint::new(2, 0)),
+            emission - 2
+        );
+
+        map.set_tile(&MapPoint::new(0, 0), DIRT_FLOOR, &state.tile_config);
+
+        assert_eq!(
+            map.light_map
+                .get_level(&MapPoint::new(0, 0)),
+            0
+        );
+        assert_eq!(
+            map.light_map
+                .get_level(&MapPoint::new(2, 0)),
+            0
+        );
+    }
+}
@@ -1,12 +1,23 @@
+use std::collections::HashMap;
 use std::fs::read_to_string;
 use std::path::Path;
 
 use rand::random;
 
+use super::chunk_graph::ChunkGraph;
+use super::light_map::LightMap;
+use super::region::Regions;
+use super::visibility::get_field_of_view;
+use super::visibility::Visibility;
 use crate::common::MapPoint;
 use crate::common::TILE_SIZE;
+use crate::movement_profile::MovementProfile;
+use crate::movement_profile::Traversability;
+use crate::screen::Animation;
+use crate::tile_config::BaseTile;
 use crate::tile_config::TileConfig;
 use crate::tile_config::TileId;
+use crate::tile_config::TileState;
 
 #[derive(Debug)]
 pub struct TilePos {
@@ -17,7 +28,11 @@ pub struct TilePos {
 #[derive(Clone)]
 pub struct Tile {
     pub tile_id: TileId,
+    /// Picks one of the animations of the current variant, which differ in number.
     pub animation_index: usize,
+    pub animation_phase: u64,
+    /// The autotile rule of the base tile matching the neighbors, if any.
+    pub variant: Option<usize>,
 }
 
 #[derive(Debug)]
@@ -28,19 +43,53 @@ pub struct Neighborhood4 {
     down: Option<TilePos>,
 }
 
+#[derive(Debug)]
+pub struct Neighborhood8 {
+    left: Option<TilePos>,
+    right: Option<TilePos>,
+    up: Option<TilePos>,
+    down: Option<TilePos>,
+    up_left: Option<TilePos>,
+    up_right: Option<TilePos>,
+    down_left: Option<TilePos>,
+    down_right: Option<TilePos>,
+}
+
+#[derive(Clone)]
 pub struct Map {
     pub tiles: Vec<Tile>,
     pub size: MapPoint,
+    pub regions: HashMap<Traversability, Regions>,
+    pub chunk_graphs: HashMap<Traversability, ChunkGraph>,
+    pub light_map: LightMap,
+    /// What the player knows about each tile, in the same order as `tiles`.
+    pub visibility: Vec<Visibility>,
+}
+
+impl Tile {
+    pub fn new(tile_id: TileId) -> Self {
+        Self {
+            tile_id,
+            animation_index: random::<u16>() as usize,
+            animation_phase: random::<u16>() as u64,
+            variant: None,
+        }
+    }
+
+    pub fn get_animation<'a>(&self, base_tile: &'a BaseTile) -> &'a Animation {
+        let animations = base_tile.get_animations(self.variant);
+        &animations[self.animation_index % animations.len()]
+    }
 }
 
 impl Neighborhood4 {
     #[rustfmt::skip]
-    pub fn filter_traversable(self, tile_config: &TileConfig) -> Self {
+    pub fn filter_traversable(self, tile_config: &TileConfig, profile: &MovementProfile) -> Self {
         Neighborhood4 {
-            left:  self. left.filter(|t| tile_config.get(t.tile_id).is_traversable()),
-            right: self.right.filter(|t| tile_config.get(t.tile_id).is_traversable()),
-            up:    self.   up.filter(|t| tile_config.get(t.tile_id).is_traversable()),
-            down:  self. down.filter(|t| tile_config.get(t.tile_id).is_traversable()),
+            left:  self. left.filter(|t| tile_config.get(t.tile_id).is_traversable(profile)),
+            right: self.right.filter(|t| tile_config.get(t.tile_id).is_traversable(profile)),
+            up:    self.   up.filter(|t| tile_config.get(t.tile_id).is_traversable(profile)),
+            down:  self. down.filter(|t| tile_config.get(t.tile_id).is_traversable(profile)),
         }
     }
 }
@@ -65,6 +114,49 @@ impl From<Neighborhood4> for Vec<TilePos> {
     }
 }
 
+impl Neighborhood8 {
+    /// Keeps the traversable neighbors. Diagonal neighbors are only kept if both
+    /// adjacent orthogonal neighbors are traversable, so paths don't cut corners.
+    #[rustfmt::skip]
+    pub fn filter_traversable(self, tile_config: &TileConfig, profile: &MovementProfile) -> Self {
+        let is_traversable = |t: &TilePos| tile_config.get(t.tile_id).is_traversable(profile);
+
+        let left  = self. left.filter(is_traversable);
+        let right = self.right.filter(is_traversable);
+        let up    = self.   up.filter(is_traversable);
+        let down  = self. down.filter(is_traversable);
+
+        Neighborhood8 {
+            up_left:    self.   up_left.filter(|t| up.is_some()   && left.is_some()  && is_traversable(t)),
+            up_right:   self.  up_right.filter(|t| up.is_some()   && right.is_some() && is_traversable(t)),
+            down_left:  self. down_left.filter(|t| down.is_some() && left.is_some()  && is_traversable(t)),
+            down_right: self.down_right.filter(|t| down.is_some() && right.is_some() && is_traversable(t)),
+            left,
+            right,
+            up,
+            down,
+        }
+    }
+}
+
+impl From<Neighborhood8> for Vec<TilePos> {
+    fn from(n: Neighborhood8) -> Self {
+        vec![
+            n.left,
+            n.right,
+            n.up,
+            n.down,
+            n.up_left,
+            n.up_right,
+            n.down_left,
+            n.down_right,
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+}
+
 impl Map {
     pub fn get_tile(&self, point: &MapPoint) -> Option<&Tile> {
         if point.x < 0 || point.x >= self.size.width() {
@@ -78,6 +170,31 @@ impl Map {
         Some(&self.tiles[(self.size.width() * point.y + point.x) as usize])
     }
 
+    pub fn get_visibility(&self, point: &MapPoint) -> Visibility {
+        if self.get_tile(point).is_none() {
+            return Visibility::Unexplored;
+        }
+
+        self.visibility[(self.size.width() * point.y + point.x) as usize]
+    }
+
+    /// Marks everything seen by the `viewers`, given as position and vision
+    /// radius, as visible. Tiles no longer in sight become remembered.
+    pub fn update_visibility(&mut self, viewers: &[(MapPoint, i32)], tile_config: &TileConfig) {
+        for visibility in &mut self.visibility {
+            if *visibility == Visibility::Visible {
+                *visibility = Visibility::Remembered;
+            }
+        }
+
+        for (pos, radius) in viewers {
+            for point in get_field_of_view(pos, *radius, self, tile_config) {
+                let i = (self.size.width() * point.y + point.x) as usize;
+                self.visibility[i] = Visibility::Visible;
+            }
+        }
+    }
+
     pub fn get_tile_pos(&self, point: &MapPoint) -> Option<TilePos> {
         if let Some(tile) = self.get_tile(point) {
             return Some(TilePos {
@@ -89,6 +206,125 @@ impl Map {
         None
     }
 
+    pub fn is_traversable(
+        &self,
+        point: &MapPoint,
+        tile_config: &TileConfig,
+        profile: &MovementProfile,
+    ) -> bool {
+        self.get_tile(point)
+            .map(|t| {
+                tile_config
+                    .get(t.tile_id)
+                    .is_traversable(profile)
+            })
+            .unwrap_or(false)
+    }
+
+    /// Whether the tile at `point` can be entered with `traversability`.
+    pub fn is_passable(
+        &self,
+        point: &MapPoint,
+        tile_config: &TileConfig,
+        traversability: &Traversability,
+    ) -> bool {
+        self.get_tile(point)
+            .map(|t| traversability.allows(tile_config.get(t.tile_id)))
+            .unwrap_or(false)
+    }
+
+    pub fn get_regions(&self, profile: &MovementProfile) -> Option<&Regions> {
+        self.regions
+            .get(&profile.traversability)
+    }
+
+    pub fn get_chunk_graph(&self, profile: &MovementProfile) -> Option<&ChunkGraph> {
+        self.chunk_graphs
+            .get(&profile.traversability)
+    }
+
+    /// Labels the regions and builds the chunk graph of the map as seen by npcs
+    /// moving with `profile`. Regions and chunk entrances are shared by profiles
+    /// with the same traversability, only path costs are kept per profile.
+    pub fn add_movement_profile(&mut self, profile: &MovementProfile, tile_config: &TileConfig) {
+        let traversability = &profile.traversability;
+
+        if !self
+            .regions
+            .contains_key(traversability)
+        {
+            let regions = Regions::new(&self.size, |p| {
+                self.is_passable(p, tile_config, traversability)
+            });
+            let chunk_graph = ChunkGraph::new(self, tile_config, traversability);
+
+            self.regions
+                .insert(traversability.clone(), regions);
+            self.chunk_graphs
+                .insert(traversability.clone(), chunk_graph);
+        }
+
+        let mut chunk_graphs = std::mem::take(&mut self.chunk_graphs);
+        if let Some(chunk_graph) = chunk_graphs.get_mut(traversability) {
+            chunk_graph.add_profile(profile, self, tile_config);
+        }
+        self.chunk_graphs = chunk_graphs;
+    }
+
+    pub fn set_tile(&mut self, point: &MapPoint, tile_id: TileId, tile_config: &TileConfig) {
+        if self.get_tile(point).is_none() {
+            return;
+        }
+
+        let i = (self.size.width() * point.y + point.x) as usize;
+        self.tiles[i] = Tile::new(tile_id);
+
+        for y in point.y - 1..=point.y + 1 {
+            for x in point.x - 1..=point.x + 1 {
+                self.update_variant(&MapPoint::new(x, y), tile_config);
+            }
+        }
+
+        let mut all_regions = std::mem::take(&mut self.regions);
+        for (traversability, regions) in &mut all_regions {
+            regions.update(point, |p| self.is_passable(p, tile_config, traversability));
+        }
+        self.regions = all_regions;
+
+        let mut chunk_graphs = std::mem::take(&mut self.chunk_graphs);
+        for (traversability, chunk_graph) in &mut chunk_graphs {
+            chunk_graph.update(point, self, tile_config, traversability);
+        }
+        self.chunk_graphs = chunk_graphs;
+
+        let mut light_map = std::mem::take(&mut self.light_map);
+        light_map.update(point, self, tile_config);
+        self.light_map = light_map;
+    }
+
+    /// Picks the first autotile rule of the tile at `point` whose exposed
+    /// neighbors are all open. Positions outside the map count as solid.
+    fn update_variant(&mut self, point: &MapPoint, tile_config: &TileConfig) {
+        let Some(tile) = self.get_tile(point) else {
+            return;
+        };
+
+        let is_open = |offset: &MapPoint| {
+            self.get_tile(&(point + offset))
+                .map(|t| tile_config.get(t.tile_id).block_state != TileState::Solid)
+                .unwrap_or(false)
+        };
+
+        let variant = tile_config
+            .get(tile.tile_id)
+            .autotile
+            .iter()
+            .position(|rule| rule.exposed.iter().all(is_open));
+
+        let i = (self.size.width() * point.y + point.x) as usize;
+        self.tiles[i].variant = variant;
+    }
+
     pub fn get_neighborhood4(&self, point: &MapPoint) -> Neighborhood4 {
         let left = self.get_tile_pos(&point.left());
         let right = self.get_tile_pos(&point.right());
@@ -103,13 +339,40 @@ impl Map {
         }
     }
 
+    pub fn get_neighborhood8(&self, point: &MapPoint) -> Neighborhood8 {
+        let left = self.get_tile_pos(&point.left());
+        let right = self.get_tile_pos(&point.right());
+        let up = self.get_tile_pos(&point.up());
+        let down = self.get_tile_pos(&point.down());
+        let up_left = self.get_tile_pos(&point.up().left());
+        let up_right = self.get_tile_pos(&point.up().right());
+        let down_left = self.get_tile_pos(&point.down().left());
+        let down_right = self.get_tile_pos(&point.down().right());
+
+        Neighborhood8 {
+            left,
+            right,
+            up,
+            down,
+            up_left,
+            up_right,
+            down_left,
+            down_right,
+        }
+    }
+
     pub fn from_file<P: AsRef<Path>>(path: P, tile_config: &TileConfig) -> Self {
         let map_toml_string = read_to_string(path).unwrap();
         let map_toml_value: toml::value::Value = toml::from_str(&map_toml_string).unwrap();
         let map_data = &map_toml_value["data"];
         let map_data_tile_ids = &map_data["tile_ids"].as_str().unwrap();
 
-        let lines = map_data_tile_ids.split_ascii_whitespace();
+        Self::from_tile_ids(map_data_tile_ids, tile_config)
+    }
+
+    /// Parses a map from lines of 3 character tile ids, as found in map files.
+    pub fn from_tile_ids(tile_ids: &str, tile_config: &TileConfig) -> Self {
+        let lines = tile_ids.split_ascii_whitespace();
 
         let mut tiles = Vec::new();
         let mut width: i32 = 0;
@@ -133,23 +396,80 @@ impl Map {
 
                 let tile_id = [ch1.unwrap(), ch2.unwrap(), ch3.unwrap()];
 
-                let max_id = tile_config
-                    .get(tile_id)
-                    .animations
-                    .len() as u8;
-
-                let animation_index = (random::<u8>() % max_id) as usize;
-
-                tiles.push(Tile {
-                    tile_id,
-                    animation_index,
-                });
+                tiles.push(Tile::new(tile_id));
             }
         }
 
-        Map {
+        let mut map = Map {
+            visibility: vec![Visibility::Unexplored; tiles.len()],
             tiles,
             size: MapPoint::new(width, height),
+            regions: HashMap::new(),
+            chunk_graphs: HashMap::new(),
+            light_map: LightMap::default(),
+        };
+
+        for y in 0..height {
+            for x in 0..width {
+                map.update_variant(&MapPoint::new(x, y), tile_config);
+            }
+        }
+
+        map.light_map = LightMap::new(&map, tile_config);
+
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::State;
+
+    const DIRT_FLOOR: [char; 3] = ['[', '_', ']'];
+
+    #[test]
+    fn test_autotile_variant_update() {
+        let state = State::new();
+        let tile_ids = "[:][:][:]\n[:][:][:]\n[_][_][_]\n";
+        let mut map = Map::from_tile_ids(tile_ids, &state.tile_config);
+
+        let wall = &state.tile_config.get(['[', ':', ']']);
+        let variant = |map: &Map, x, y| {
+            map.get_tile(&MapPoint::new(x, y))
+                .unwrap()
+                .variant
+        };
+
+        assert_eq!(variant(&map, 1, 0), None);
+        assert_eq!(
+            wall.autotile[variant(&map, 1, 1).unwrap()].exposed,
+            vec![MapPoint::new(0, 1)]
+        );
+
+        map.set_tile(&MapPoint::new(1, 1), DIRT_FLOOR, &state.tile_config);
+
+        assert_eq!(
+            wall.autotile[variant(&map, 1, 0).unwrap()].exposed,
+            vec![MapPoint::new(0, 1)]
+        );
+        assert_eq!(variant(&map, 1, 1), None);
+    }
+
+    #[test]
+    fn test_every_variant_animation_is_drawn() {
+        let state = State::new();
+        let tile_ids = "[:][:][:]\n[:][:][:]\n[_][_][_]\n";
+        let mut map = Map::from_tile_ids(tile_ids, &state.tile_config);
+
+        let wall = state.tile_config.get(['[', ':', ']']);
+        let tile = &mut map.tiles[4];
+        let animations = wall.get_animations(tile.variant);
+        assert_eq!(animations.len(), 3);
+
+        for (i, animation) in animations.iter().enumerate() {
+            tile.animation_index = i;
+            assert!(std::ptr::eq(tile.get_animation(wall), animation));
         }
     }
 }
@@ -0,0 +1,162 @@
+use std::collections::VecDeque;
+use std::fmt::Display;
+
+/// How many messages are kept, older ones are dropped.
+pub const MESSAGE_LOG_CAPACITY: usize = 500;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MessageCategory {
+    Job,
+    Movement,
+    System,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+    /// The `elapsed_time` the message was posted at.
+    pub time: u64,
+    pub category: MessageCategory,
+    pub severity: Severity,
+    pub text: String,
+}
+
+#[derive(Debug, Default)]
+pub struct MessageLog {
+    messages: VecDeque<Message>,
+    /// How many messages the scrollback view is scrolled up from the newest one.
+    pub scroll: usize,
+    pub is_open: bool,
+}
+
+impl Display for MessageCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            MessageCategory::Job => "job",
+            MessageCategory::Movement => "move",
+            MessageCategory::System => "system",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+impl Display for Message {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{:>5}] {:<6} {}", self.time, self.category, self.text)
+    }
+}
+
+impl MessageLog {
+    pub fn post(&mut self, message: Message) {
+        if self.messages.len() >= MESSAGE_LOG_CAPACITY {
+            self.messages.pop_front();
+        }
+
+        self.messages.push_back(message);
+
+        // keeps the scrollback view on the same messages
+        if self.scroll > 0 {
+            self.scroll = (self.scroll + 1).min(self.messages.len() - 1);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// Up to `count` messages ending `scroll` messages before the newest one,
+    /// oldest first.
+    pub fn get_page(&self, count: usize, scroll: usize) -> impl Iterator<Item = &Message> {
+        let end = self
+            .messages
+            .len()
+            .saturating_sub(scroll);
+        let start = end.saturating_sub(count);
+
+        self.messages.range(start..end)
+    }
+
+    pub fn toggle(&mut self) {
+        self.is_open = !self.is_open;
+        self.scroll = 0;
+    }
+
+    pub fn scroll_up(&mut self, count: usize) {
+        self.scroll = (self.scroll + count).min(self.messages.len().saturating_sub(1));
+    }
+
+    pub fn scroll_down(&mut self, count: usize) {
+        self.scroll = self.scroll.saturating_sub(count);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::MapPoint;
+    use crate::state::task::goto::GotoTask;
+    use crate::state::State;
+
+    fn get_message(time: u64) -> Message {
+        Message {
+            time,
+            category: MessageCategory::System,
+            severity: Severity::Info,
+            text: format!("message {time}"),
+        }
+    }
+
+    #[test]
+    fn test_message_log_scroll_and_capacity() {
+        let mut message_log = MessageLog::default();
+
+        for time in 0..(MESSAGE_LOG_CAPACITY as u64 + 10) {
+            message_log.post(get_message(time));
+        }
+        assert_eq!(message_log.len(), MESSAGE_LOG_CAPACITY);
+
+        let times: Vec<u64> = message_log
+            .get_page(3, 0)
+            .map(|m| m.time)
+            .collect();
+        assert_eq!(times, vec![507, 508, 509]);
+
+        message_log.scroll_up(5);
+        message_log.post(get_message(510));
+
+        let times: Vec<u64> = message_log
+            .get_page(3, message_log.scroll)
+            .map(|m| m.time)
+            .collect();
+        assert_eq!(times, vec![502, 503, 504]);
+    }
+
+    #[test]
+    fn test_unreachable_goal_posts_message() {
+        let mut state = State::new();
+
+        state
+            .cursor_tasks
+            .push(Box::new(GotoTask::new(MapPoint::new(18, 7))));
+
+        for _ in 0..3 {
+            state.update_npcs();
+        }
+
+        let messages: Vec<&Message> = state
+            .message_log
+            .get_page(10, 0)
+            .collect();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].category, MessageCategory::Movement);
+        assert_eq!(messages[0].severity, Severity::Warning);
+        assert_eq!(messages[0].text, "Follower cannot reach target at 18, 7");
+    }
+}
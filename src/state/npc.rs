@@ -7,6 +7,9 @@ use crate::npc_config::NpcId;
 #[derive(Clone)]
 pub struct Npc {
     pub animation: NpcAnimationId,
+    pub animation_phase: u64,
+    /// Position in `State::npcs`.
+    pub index: usize,
     pub npc_id: NpcId,
     pub pos: MapPoint,
     pub task: Box<dyn Task>,
@@ -20,6 +23,22 @@ pub enum NpcAnimationId {
     Attack,
 }
 
+#[derive(Debug, PartialEq)]
+pub enum Faction {
+    Player,
+    Hostile,
+}
+
+impl From<&str> for Faction {
+    fn from(key: &str) -> Self {
+        match key {
+            "player" => Faction::Player,
+            "hostile" => Faction::Hostile,
+            _ => panic!("Faction '{key}' unknown."),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum NpcClass {
     Debug,
@@ -51,6 +70,8 @@ impl Npc {
             return;
         }
 
+        state.occupancy.release(self.index);
+
         match state
             .npc_config
             .get(&self.npc_id)
@@ -62,8 +83,8 @@ impl Npc {
         }
     }
 
-    pub fn assign(&mut self, task: Box<dyn Task>) {
-        task.assign(self);
+    pub fn assign(&mut self, task: Box<dyn Task>, state: &mut State) {
+        task.assign(self, state);
     }
 
     fn set_idle_cursor_task(&mut self) {
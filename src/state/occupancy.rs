@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+
+use super::npc::Npc;
+use crate::common::MapPoint;
+
+/// Tracks which npc stands on which tile, at most one per tile, and which
+/// tile a blocked npc is waiting to step onto.
+#[derive(Debug, Default)]
+pub struct Occupancy {
+    standing: HashMap<MapPoint, usize>,
+    reservations: HashMap<usize, MapPoint>,
+}
+
+impl Occupancy {
+    pub fn new(npcs: &[Npc]) -> Self {
+        let standing = npcs
+            .iter()
+            .map(|n| (n.pos.clone(), n.index))
+            .collect();
+
+        Self {
+            standing,
+            reservations: HashMap::new(),
+        }
+    }
+
+    pub fn get(&self, point: &MapPoint) -> Option<usize> {
+        self.standing.get(point).copied()
+    }
+
+    pub fn is_occupied(&self, point: &MapPoint) -> bool {
+        self.standing.contains_key(point)
+    }
+
+    pub fn get_reservation(&self, npc_index: usize) -> Option<&MapPoint> {
+        self.reservations.get(&npc_index)
+    }
+
+    pub fn reserve(&mut self, npc_index: usize, point: MapPoint) {
+        self.reservations
+            .insert(npc_index, point);
+    }
+
+    pub fn release(&mut self, npc_index: usize) {
+        self.reservations.remove(&npc_index);
+    }
+
+    pub fn move_npc(&mut self, npc_index: usize, from: &MapPoint, to: MapPoint) {
+        if self.get(from) == Some(npc_index) {
+            self.standing.remove(from);
+        }
+
+        self.standing.insert(to, npc_index);
+        self.release(npc_index);
+    }
+
+    pub fn swap(&mut self, a: &MapPoint, b: &MapPoint) {
+        let npc_a = self.standing.remove(a);
+        let npc_b = self.standing.remove(b);
+
+        if let Some(npc_a) = npc_a {
+            self.standing.insert(b.clone(), npc_a);
+            self.release(npc_a);
+        }
+
+        if let Some(npc_b) = npc_b {
+            self.standing.insert(a.clone(), npc_b);
+            self.release(npc_b);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::message_log::Message;
+    use crate::state::task::goto::GotoTask;
+    use crate::state::task::goto_flow::GotoFlowTask;
+    use crate::state::task::Task;
+    use crate::state::State;
+
+    #[test]
+    fn test_npcs_swap_places() {
+        let mut state = State::new();
+        let a = MapPoint::new(9, 11);
+        let b = MapPoint::new(10, 11);
+
+        state.npcs.truncate(2);
+        state.npcs[0].pos = a.clone();
+        state.npcs[1].pos = b.clone();
+        state.occupancy = Occupancy::new(&state.npcs);
+
+        state
+            .cursor_tasks
+            .push(Box::new(GotoTask::new(b.clone())));
+        state
+            .cursor_tasks
+            .push(Box::new(GotoTask::new(a.clone())));
+
+        for _ in 0..6 {
+            state.update_npcs();
+            assert_ne!(state.npcs[0].pos, state.npcs[1].pos);
+        }
+
+        assert_eq!(state.npcs[0].pos, b);
+        assert_eq!(state.npcs[1].pos, a);
+    }
+
+    #[test]
+    fn test_npc_waits_for_occupied_goal() {
+        let mut state = State::new();
+        let a = MapPoint::new(9, 11);
+        let b = MapPoint::new(10, 11);
+
+        state.npcs.truncate(2);
+        state.npcs[0].pos = a.clone();
+        state.npcs[1].pos = b.clone();
+        state.occupancy = Occupancy::new(&state.npcs);
+
+        state
+            .cursor_tasks
+            .push(Box::new(GotoTask::new(b.clone())));
+
+        for _ in 0..10 {
+            state.update_npcs();
+            assert_ne!(state.npcs[0].pos, state.npcs[1].pos);
+        }
+
+        assert_eq!(state.npcs[0].pos, a);
+        assert_eq!(state.npcs[1].pos, b);
+        assert_eq!(state.occupancy.get(&a), Some(0));
+        assert_eq!(state.occupancy.get(&b), Some(1));
+    }
+
+    /// Sends the two followers to the tile the imp stands on.
+    fn block_at_same_goal(task: fn(MapPoint) -> Box<dyn Task>) {
+        let mut state = State::new();
+        let goal = MapPoint::new(11, 11);
+
+        state.npcs.truncate(3);
+        state.npcs[0].pos = MapPoint::new(9, 11);
+        state.npcs[1].pos = MapPoint::new(10, 13);
+        state.npcs[2].pos = goal.clone();
+        state.occupancy = Occupancy::new(&state.npcs);
+
+        for _ in 0..2 {
+            state
+                .cursor_tasks
+                .push(task(goal.clone()));
+        }
+
+        for _ in 0..30 {
+            state.update_npcs();
+        }
+
+        assert!(state.cursor_tasks.is_empty());
+        assert_eq!(state.npcs[0].task.get_name(), "IdleCursor");
+        assert_eq!(state.npcs[1].task.get_name(), "IdleCursor");
+        assert_eq!(state.occupancy.get(&goal), Some(2));
+
+        let messages: Vec<&Message> = state
+            .message_log
+            .get_page(10, 0)
+            .collect();
+        assert_eq!(messages.len(), 2);
+    }
+
+    #[test]
+    fn test_npcs_blocked_at_same_goal_give_up() {
+        block_at_same_goal(|goal| Box::new(GotoTask::new(goal)));
+        block_at_same_goal(|goal| Box::new(GotoFlowTask::new(goal)));
+    }
+}
@@ -0,0 +1,375 @@
+use std::collections::HashSet;
+
+use super::chunk_graph::CHUNK_SIZE;
+use super::map::Map;
+use super::map::TilePos;
+use crate::common::MapPoint;
+use crate::movement_profile::MovementProfile;
+use crate::tile_config::TileConfig;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PathError {
+    /// The goal lies in a different region than the start, no search was done.
+    Unreachable,
+    /// The search finished without finding the goal.
+    NotFound,
+}
+
+/// Finds the cheapest path from `start` to `goal`. Long paths are taken from the
+/// chunk graph of the profile if there is one, and may be slightly more expensive.
+pub fn get_shortest_path(
+    start: &MapPoint,
+    goal: &MapPoint,
+    map: &Map,
+    tile_config: &TileConfig,
+    profile: &MovementProfile,
+) -> Result<Vec<MapPoint>, PathError> {
+    get_shortest_path_avoiding(start, goal, map, tile_config, profile, &HashSet::new())
+}
+
+/// Like `get_shortest_path`, but treats the points in `avoid` as not traversable,
+/// e.g. to route around other npcs.
+pub fn get_shortest_path_avoiding(
+    start: &MapPoint,
+    goal: &MapPoint,
+    map: &Map,
+    tile_config: &TileConfig,
+    profile: &MovementProfile,
+    avoid: &HashSet<MapPoint>,
+) -> Result<Vec<MapPoint>, PathError> {
+    if avoid.contains(goal) {
+        return Err(PathError::Unreachable);
+    }
+
+    if let Some(regions) = map.get_regions(profile) {
+        if !regions.is_reachable(start, goal) {
+            return Err(PathError::Unreachable);
+        }
+    }
+
+    // long paths are searched on the chunk graph first, unless some points
+    // have to be avoided, which the chunk graph does not know about
+    let distance = pathfinding::prelude::absdiff(start.x, goal.x)
+        + pathfinding::prelude::absdiff(start.y, goal.y);
+
+    if avoid.is_empty() && distance > 2 * CHUNK_SIZE {
+        if let Some(chunk_graph) = map.get_chunk_graph(profile) {
+            if let Some(path) = chunk_graph.get_path(start, goal, map, tile_config, profile) {
+                return Ok(path);
+            }
+        }
+    }
+
+    let min_move_cost = profile.min_move_cost(tile_config);
+
+    let path = pathfinding::prelude::astar(
+        start,
+        |p| {
+            let mut s = successors(p, map, tile_config, profile);
+            s.retain(|(p, _)| !avoid.contains(p));
+            s
+        },
+        |p| heuristic(p, goal, min_move_cost, profile.diagonal),
+        |p| p == goal,
+    );
+
+    path.map(|p| p.0)
+        .ok_or(PathError::NotFound)
+}
+
+/// A path ending next to one of several targets.
+#[derive(Debug, PartialEq)]
+pub struct AdjacentPath {
+    pub steps: Vec<MapPoint>,
+    /// The target next to the last step.
+    pub target: MapPoint,
+}
+
+/// Finds the shortest path to any tile orthogonally adjacent to one of `targets`,
+/// which don't need to be traversable themselves, e.g. a wall to be mined.
+pub fn get_shortest_path_to_adjacent(
+    start: &MapPoint,
+    targets: &[MapPoint],
+    map: &Map,
+    tile_config: &TileConfig,
+    profile: &MovementProfile,
+) -> Result<AdjacentPath, PathError> {
+    let regions = map.get_regions(profile);
+
+    let goals: HashSet<MapPoint> = targets
+        .iter()
+        .flat_map(|t| [t.left(), t.right(), t.up(), t.down()])
+        .filter(|p| map.is_traversable(p, tile_config, profile))
+        .filter(|p| {
+            regions
+                .map(|r| r.is_reachable(start, p))
+                .unwrap_or(true)
+        })
+        .collect();
+
+    if goals.is_empty() {
+        return Err(PathError::Unreachable);
+    }
+
+    let min_move_cost = profile.min_move_cost(tile_config);
+
+    // every goal lies within the bounding box, so the distance to it never
+    // overestimates and is computed in constant time
+    let x1 = goals.iter().map(|g| g.x).min().unwrap();
+    let x2 = goals.iter().map(|g| g.x).max().unwrap();
+    let y1 = goals.iter().map(|g| g.y).min().unwrap();
+    let y2 = goals.iter().map(|g| g.y).max().unwrap();
+
+    let path = pathfinding::prelude::astar(
+        start,
+        |p| successors(p, map, tile_config, profile),
+        |p| {
+            let nearest = MapPoint::new(p.x.clamp(x1, x2), p.y.clamp(y1, y2));
+            heuristic(p, &nearest, min_move_cost, profile.diagonal)
+        },
+        |p| goals.contains(p),
+    );
+
+    let steps = path
+        .map(|p| p.0)
+        .ok_or(PathError::NotFound)?;
+
+    let last_step = steps.last().unwrap();
+    let target = targets
+        .iter()
+        .find(|t| {
+            pathfinding::prelude::absdiff(t.x, last_step.x)
+                + pathfinding::prelude::absdiff(t.y, last_step.y)
+                == 1
+        })
+        .unwrap()
+        .clone();
+
+    Ok(AdjacentPath { steps, target })
+}
+
+fn successors(
+    point: &MapPoint,
+    map: &Map,
+    tile_config: &TileConfig,
+    profile: &MovementProfile,
+) -> Vec<(MapPoint, u32)> {
+    get_traversable_neighbors(point, map, tile_config, profile)
+        .iter()
+        .map(|t| (t.pos.clone(), get_step_cost(point, t, tile_config, profile)))
+        .collect()
+}
+
+pub fn get_traversable_neighbors(
+    point: &MapPoint,
+    map: &Map,
+    tile_config: &TileConfig,
+    profile: &MovementProfile,
+) -> Vec<TilePos> {
+    if profile.diagonal {
+        map.get_neighborhood8(point)
+            .filter_traversable(tile_config, profile)
+            .into()
+    } else {
+        map.get_neighborhood4(point)
+            .filter_traversable(tile_config, profile)
+            .into()
+    }
+}
+
+/// The cost of stepping from `from` onto the neighboring tile `to`.
+pub fn get_step_cost(
+    from: &MapPoint,
+    to: &TilePos,
+    tile_config: &TileConfig,
+    profile: &MovementProfile,
+) -> u32 {
+    let move_cost = profile.move_cost(tile_config.get(to.tile_id));
+
+    if to.pos.x != from.x && to.pos.y != from.y {
+        diagonal_cost(move_cost)
+    } else {
+        move_cost
+    }
+}
+
+/// Approximates the cost of a diagonal step as `move_cost * sqrt(2)`, rounded down.
+fn diagonal_cost(move_cost: u32) -> u32 {
+    move_cost * 14 / 10
+}
+
+/// Manhattan distance, or octile distance if diagonal steps are allowed, scaled
+/// by the cheapest move cost, so it never overestimates.
+pub fn heuristic(point: &MapPoint, goal: &MapPoint, min_move_cost: u32, diagonal: bool) -> u32 {
+    let dx = pathfinding::prelude::absdiff(point.x, goal.x) as u32;
+    let dy = pathfinding::prelude::absdiff(point.y, goal.y) as u32;
+
+    if diagonal {
+        let straight = dx.max(dy) - dx.min(dy);
+        let diagonal = dx.min(dy);
+
+        straight * min_move_cost + diagonal * diagonal_cost(min_move_cost)
+    } else {
+        (dx + dy) * min_move_cost
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::State;
+
+    const DIRT_ROAD: [char; 3] = ['[', '=', ']'];
+    const RUBBLE: [char; 3] = ['[', '%', ']'];
+
+    #[test]
+    fn test_get_shortest_path_same_region() {
+        let state = State::new();
+
+        let path = get_shortest_path(
+            &MapPoint::new(2, 2),
+            &MapPoint::new(3, 3),
+            &state.map,
+            &state.tile_config,
+            &MovementProfile::default(),
+        );
+
+        assert_eq!(path.map(|p| p.len()), Ok(3));
+    }
+
+    #[test]
+    fn test_get_shortest_path_unreachable() {
+        let state = State::new();
+
+        let path = get_shortest_path(
+            &MapPoint::new(2, 2),
+            &MapPoint::new(18, 7),
+            &state.map,
+            &state.tile_config,
+            &MovementProfile::default(),
+        );
+        assert_eq!(path, Err(PathError::Unreachable));
+
+        let path = get_shortest_path(
+            &MapPoint::new(2, 2),
+            &MapPoint::new(1, 1),
+            &state.map,
+            &state.tile_config,
+            &MovementProfile::default(),
+        );
+        assert_eq!(path, Err(PathError::Unreachable));
+    }
+
+    #[test]
+    fn test_get_shortest_path_prefers_cheap_tiles() {
+        let mut state = State::new();
+        let start = MapPoint::new(9, 10);
+        let goal = MapPoint::new(12, 13);
+
+        // a road along the bottom and right edge of the room is cheaper than the
+        // staircase through its middle, even though it is not shorter
+        for p in [(9, 11), (9, 12), (9, 13), (10, 13), (11, 13)] {
+            state.set_tile(&MapPoint::new(p.0, p.1), DIRT_ROAD);
+        }
+        state.set_tile(&MapPoint::new(10, 11), RUBBLE);
+        state.set_tile(&MapPoint::new(11, 12), RUBBLE);
+
+        let path = get_shortest_path(
+            &start,
+            &goal,
+            &state.map,
+            &state.tile_config,
+            &MovementProfile::default(),
+        )
+        .unwrap();
+
+        assert_eq!(path.len(), 7);
+        assert!(path.contains(&MapPoint::new(9, 13)));
+    }
+
+    #[test]
+    fn test_get_shortest_path_through_walls() {
+        let state = State::new();
+        let ghost_profile = &state
+            .npc_config
+            .get(&String::from("ghost"))
+            .movement_profile;
+
+        let path = get_shortest_path(
+            &MapPoint::new(2, 2),
+            &MapPoint::new(18, 7),
+            &state.map,
+            &state.tile_config,
+            ghost_profile,
+        );
+
+        assert_eq!(path.map(|p| p.len()), Ok(22));
+    }
+
+    #[test]
+    fn test_get_shortest_path_diagonal() {
+        let state = State::new();
+        let profile = MovementProfile {
+            diagonal: true,
+            ..MovementProfile::default()
+        };
+
+        let path = get_shortest_path(
+            &MapPoint::new(2, 2),
+            &MapPoint::new(3, 3),
+            &state.map,
+            &state.tile_config,
+            &profile,
+        );
+        assert_eq!(path.map(|p| p.len()), Ok(2));
+
+        // the wall at (6, 6) forbids cutting the corner
+        let path = get_shortest_path(
+            &MapPoint::new(5, 6),
+            &MapPoint::new(6, 5),
+            &state.map,
+            &state.tile_config,
+            &profile,
+        );
+        assert_eq!(path.map(|p| p.len()), Ok(3));
+    }
+
+    #[test]
+    fn test_get_shortest_path_to_adjacent() {
+        let state = State::new();
+        let profile = MovementProfile::default();
+
+        let path = get_shortest_path_to_adjacent(
+            &MapPoint::new(2, 2),
+            &[MapPoint::new(1, 2)],
+            &state.map,
+            &state.tile_config,
+            &profile,
+        )
+        .unwrap();
+        assert_eq!(path.steps, vec![MapPoint::new(2, 2)]);
+        assert_eq!(path.target, MapPoint::new(1, 2));
+
+        // the wall at (20, 9) is only adjacent to the right hall
+        let path = get_shortest_path_to_adjacent(
+            &MapPoint::new(9, 10),
+            &[MapPoint::new(20, 9), MapPoint::new(14, 13)],
+            &state.map,
+            &state.tile_config,
+            &profile,
+        )
+        .unwrap();
+        assert_eq!(path.steps.len(), 8);
+        assert_eq!(path.steps.last(), Some(&MapPoint::new(13, 13)));
+        assert_eq!(path.target, MapPoint::new(14, 13));
+
+        let path = get_shortest_path_to_adjacent(
+            &MapPoint::new(9, 10),
+            &[MapPoint::new(20, 9)],
+            &state.map,
+            &state.tile_config,
+            &profile,
+        );
+        assert_eq!(path, Err(PathError::Unreachable));
+    }
+}
@@ -0,0 +1,167 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::time::Duration;
+use std::time::Instant;
+
+use super::map::Map;
+use super::path::get_shortest_path_avoiding;
+use super::path::PathError;
+use crate::common::MapPoint;
+use crate::movement_profile::MovementProfile;
+use crate::tile_config::TileConfig;
+
+/// Time per tick spent on solving queued path requests, checked between requests.
+pub const PATH_QUEUE_BUDGET: Duration = Duration::from_millis(10);
+
+pub type PathResult = Result<Vec<MapPoint>, PathError>;
+
+/// Receives the result of a queued path request once it is solved.
+#[derive(Clone, Default)]
+pub struct PathHandle {
+    result: Rc<RefCell<Option<PathResult>>>,
+}
+
+impl PathHandle {
+    pub fn is_ready(&self) -> bool {
+        self.result.borrow().is_some()
+    }
+
+    pub fn take(&self) -> Option<PathResult> {
+        self.result.borrow_mut().take()
+    }
+}
+
+struct PathRequest {
+    start: MapPoint,
+    goal: MapPoint,
+    profile: MovementProfile,
+    avoid: HashSet<MapPoint>,
+    handle: PathHandle,
+}
+
+/// Path requests waiting to be solved, oldest first.
+#[derive(Default)]
+pub struct PathQueue {
+    requests: VecDeque<PathRequest>,
+}
+
+impl PathQueue {
+    pub fn request(
+        &mut self,
+        start: MapPoint,
+        goal: MapPoint,
+        profile: MovementProfile,
+        avoid: HashSet<MapPoint>,
+    ) -> PathHandle {
+        let handle = PathHandle::default();
+
+        self.requests.push_back(PathRequest {
+            start,
+            goal,
+            profile,
+            avoid,
+            handle: handle.clone(),
+        });
+
+        handle
+    }
+
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    /// Solves requests until `budget` is used up, but at least one, so the queue
+    /// always makes progress. Returns the number of solved requests.
+    ///
+    /// The budget is only checked between requests, a single search is never
+    /// interrupted. Long range searches stay short as they run on the chunk graph.
+    pub fn solve(&mut self, map: &Map, tile_config: &TileConfig, budget: Duration) -> usize {
+        let start_time = Instant::now();
+        let mut solved = 0;
+
+        while let Some(request) = self.requests.pop_front() {
+            // nobody is waiting for the result anymore
+            if Rc::strong_count(&request.handle.result) == 1 {
+                continue;
+            }
+
+            let path = get_shortest_path_avoiding(
+                &request.start,
+                &request.goal,
+                map,
+                tile_config,
+                &request.profile,
+                &request.avoid,
+            );
+
+            *request.handle.result.borrow_mut() = Some(path);
+            solved += 1;
+
+            if start_time.elapsed() >= budget {
+                break;
+            }
+        }
+
+        solved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::State;
+
+    #[test]
+    fn test_path_queue_respects_budget() {
+        let state = State::new();
+        let mut path_queue = PathQueue::default();
+
+        let handle1 = path_queue.request(
+            MapPoint::new(2, 2),
+            MapPoint::new(3, 3),
+            MovementProfile::default(),
+            HashSet::new(),
+        );
+        let handle2 = path_queue.request(
+            MapPoint::new(2, 2),
+            MapPoint::new(18, 7),
+            MovementProfile::default(),
+            HashSet::new(),
+        );
+
+        let solved = path_queue.solve(&state.map, &state.tile_config, Duration::ZERO);
+        assert_eq!(solved, 1);
+        assert!(handle1.is_ready());
+        assert!(!handle2.is_ready());
+
+        let solved = path_queue.solve(&state.map, &state.tile_config, Duration::ZERO);
+        assert_eq!(solved, 1);
+        assert_eq!(
+            handle1
+                .take()
+                .map(|p| p.map(|s| s.len())),
+            Some(Ok(3))
+        );
+        assert_eq!(handle2.take(), Some(Err(PathError::Unreachable)));
+        assert_eq!(path_queue.len(), 0);
+    }
+
+    #[test]
+    fn test_path_queue_skips_abandoned_requests() {
+        let state = State::new();
+        let mut path_queue = PathQueue::default();
+
+        drop(path_queue.request(
+            MapPoint::new(2, 2),
+            MapPoint::new(3, 3),
+            MovementProfile::default(),
+            HashSet::new(),
+        ));
+
+        let solved = path_queue.solve(&state.map, &state.tile_config, Duration::ZERO);
+        assert_eq!(solved, 0);
+        assert_eq!(path_queue.len(), 0);
+    }
+}
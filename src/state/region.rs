@@ -0,0 +1,200 @@
+use std::collections::HashSet;
+
+use super::flood_fill::flood_fill;
+use crate::common::MapPoint;
+
+pub type RegionId = usize;
+
+/// Labels every traversable tile with the id of its connected component,
+/// so reachability between two tiles is a simple comparison.
+#[derive(Clone, Debug, Default)]
+pub struct Regions {
+    labels: Vec<Option<RegionId>>,
+    next_id: RegionId,
+    size: MapPoint,
+}
+
+impl Regions {
+    pub fn new<F>(size: &MapPoint, is_traversable: F) -> Self
+    where
+        F: Fn(&MapPoint) -> bool,
+    {
+        let mut regions = Self {
+            labels: vec![None; (size.width() * size.height()) as usize],
+            next_id: 0,
+            size: size.clone(),
+        };
+
+        for y in 0..size.height() {
+            for x in 0..size.width() {
+                let point = MapPoint::new(x, y);
+
+                if regions.get(&point).is_none() && is_traversable(&point) {
+                    regions.label(point, &is_traversable);
+                }
+            }
+        }
+
+        regions
+    }
+
+    pub fn get(&self, point: &MapPoint) -> Option<RegionId> {
+        self.index(point)
+            .and_then(|i| self.labels[i])
+    }
+
+    pub fn is_reachable(&self, from: &MapPoint, to: &MapPoint) -> bool {
+        match (self.get(from), self.get(to)) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    /// Relabels the regions touching `point` after its tile has changed.
+    /// Only the components adjacent to `point` are visited.
+    pub fn update<F>(&mut self, point: &MapPoint, is_traversable: F)
+    where
+        F: Fn(&MapPoint) -> bool,
+    {
+        let i = match self.index(point) {
+            Some(i) => i,
+            None => return,
+        };
+
+        if self.labels[i].is_some() == is_traversable(point) {
+            return;
+        }
+
+        self.labels[i] = None;
+
+        let mut relabeled = HashSet::new();
+        let candidates = [
+            point.clone(),
+            point.left(),
+            point.right(),
+            point.up(),
+            point.down(),
+        ];
+
+        for candidate in candidates {
+            if relabeled.contains(&candidate) || !is_traversable(&candidate) {
+                continue;
+            }
+
+            relabeled.extend(self.label(candidate, &is_traversable));
+        }
+    }
+
+    fn label<F>(&mut self, start: MapPoint, is_traversable: F) -> HashSet<MapPoint>
+    where
+        F: Fn(&MapPoint) -> bool,
+    {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let fill = flood_fill(start, |p| self.index(p).is_some() && is_traversable(p));
+
+        for point in &fill {
+            let i = self.index(point).unwrap();
+            self.labels[i] = Some(id);
+        }
+
+        fill
+    }
+
+    fn index(&self, point: &MapPoint) -> Option<usize> {
+        if point.x < 0 || point.x >= self.size.width() {
+            return None;
+        }
+
+        if point.y < 0 || point.y >= self.size.height() {
+            return None;
+        }
+
+        Some((self.size.width() * point.y + point.x) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movement_profile::MovementProfile;
+    use crate::state::State;
+
+    const DIRT_FLOOR: [char; 3] = ['[', '_', ']'];
+    const DIRT_WALL: [char; 3] = ['[', ':', ']'];
+
+    #[test]
+    fn test_regions_match_flood_fill() {
+        let state = State::new();
+        let profile = MovementProfile::default();
+        let regions = state.map.get_regions(&profile).unwrap();
+
+        let fill = state.flood_fill_map(MapPoint::new(2, 2), &profile);
+        let id = regions.get(&MapPoint::new(2, 2));
+
+        assert!(id.is_some());
+        assert!(fill
+            .iter()
+            .all(|p| regions.get(p) == id));
+        assert_eq!(regions.get(&MapPoint::new(0, 0)), None);
+    }
+
+    #[test]
+    fn test_regions_is_reachable() {
+        let state = State::new();
+        let regions = state
+            .map
+            .get_regions(&MovementProfile::default())
+            .unwrap();
+
+        assert!(regions.is_reachable(&MapPoint::new(2, 2), &MapPoint::new(3, 3)));
+        assert!(regions.is_reachable(&MapPoint::new(18, 7), &MapPoint::new(19, 7)));
+        assert!(!regions.is_reachable(&MapPoint::new(2, 2), &MapPoint::new(18, 7)));
+        assert!(!regions.is_reachable(&MapPoint::new(2, 2), &MapPoint::new(1, 1)));
+    }
+
+    #[test]
+    fn test_regions_update_merge_and_split() {
+        let mut state = State::new();
+
+        for x in 14..18 {
+            state.set_tile(&MapPoint::new(x, 12), DIRT_FLOOR);
+        }
+
+        let regions = state
+            .map
+            .get_regions(&MovementProfile::default())
+            .unwrap();
+        assert!(regions.is_reachable(&MapPoint::new(2, 2), &MapPoint::new(18, 7)));
+        assert!(regions.is_reachable(&MapPoint::new(2, 2), &MapPoint::new(15, 12)));
+
+        state.set_tile(&MapPoint::new(15, 12), DIRT_WALL);
+
+        let regions = state
+            .map
+            .get_regions(&MovementProfile::default())
+            .unwrap();
+        assert!(!regions.is_reachable(&MapPoint::new(2, 2), &MapPoint::new(18, 7)));
+        assert!(regions.is_reachable(&MapPoint::new(2, 2), &MapPoint::new(14, 12)));
+        assert!(regions.is_reachable(&MapPoint::new(16, 12), &MapPoint::new(18, 7)));
+        assert_eq!(regions.get(&MapPoint::new(15, 12)), None);
+    }
+
+    #[test]
+    fn test_regions_shared_by_traversability() {
+        let state = State::new();
+        let mut map = (*state.map).clone();
+        let regions_total = map.regions.len();
+
+        let profile = MovementProfile {
+            diagonal: true,
+            move_costs: [(String::from("rubble"), 99)].into(),
+            ..MovementProfile::default()
+        };
+        map.add_movement_profile(&profile, &state.tile_config);
+
+        assert_eq!(map.regions.len(), regions_total);
+        assert!(map.get_regions(&profile).is_some());
+    }
+}
@@ -2,8 +2,13 @@ use std::fmt::Display;
 
 use super::npc::Npc;
 use super::State;
+use crate::common::MapPoint;
+
+/// How many ticks an npc waits for a blocked tile before looking for another way.
+pub const MAX_BLOCKED_TICKS: u32 = 4;
 
 pub mod goto;
+pub mod goto_flow;
 pub mod idle;
 
 pub trait Action {
@@ -11,9 +16,15 @@ pub trait Action {
 }
 
 pub trait Task: TaskClone + Iterator<Item = Box<dyn Action>> {
-    fn assign(self: Box<Self>, npc: &mut Npc);
+    fn assign(self: Box<Self>, npc: &mut Npc, state: &mut State);
     fn get_name(&self) -> String;
     fn get_priority(&self) -> i32;
+
+    /// Called when the last action could not be executed because another npc
+    /// stands on the next tile or the tile is no longer traversable. `pos` is
+    /// where the npc is waiting. Tasks which move npcs have to give up when
+    /// still blocked after `MAX_BLOCKED_TICKS`, so no npc waits forever.
+    fn blocked(&mut self, _pos: &MapPoint, _state: &mut State) {}
 }
 
 // Magic necessary for making trait objects clonable
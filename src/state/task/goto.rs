@@ -1,49 +1,136 @@
-use std::rc::Rc;
+use std::collections::HashSet;
 
 use super::Action;
 use super::Task;
+use super::MAX_BLOCKED_TICKS;
 use crate::common::MapPoint;
-use crate::state::get_shortest_path;
-use crate::state::map::Map;
+use crate::movement_profile::MovementProfile;
+use crate::state::message_log::MessageCategory;
+use crate::state::message_log::Severity;
 use crate::state::npc::Npc;
+use crate::state::path_queue::PathHandle;
 use crate::state::State;
-use crate::tile_config::TileConfig;
 
 pub struct GotoAction {
     next_step: MapPoint,
 }
 
+impl GotoAction {
+    pub fn new(next_step: MapPoint) -> Self {
+        Self { next_step }
+    }
+}
+
 impl Action for GotoAction {
-    fn execute(&self, npc: &mut Npc, _state: &mut State) {
-        npc.pos = self.next_step.clone();
+    fn execute(&self, npc: &mut Npc, state: &mut State) {
+        if self.next_step == npc.pos {
+            return;
+        }
+
+        // the map changed since the step was planned
+        let profile = &state.get_base_npc(npc).movement_profile;
+        if !state.is_tile_traversable(&self.next_step, profile) {
+            npc.task.blocked(&npc.pos, state);
+            return;
+        }
+
+        match state.occupancy.get(&self.next_step) {
+            None => {
+                state
+                    .occupancy
+                    .move_npc(npc.index, &npc.pos, self.next_step.clone());
+                npc.pos = self.next_step.clone();
+            }
+            Some(other) if state.occupancy.get_reservation(other) == Some(&npc.pos) => {
+                state
+                    .occupancy
+                    .swap(&npc.pos, &self.next_step);
+                state.npcs[other].pos = npc.pos.clone();
+                npc.pos = self.next_step.clone();
+            }
+            Some(_) => {
+                state
+                    .occupancy
+                    .reserve(npc.index, self.next_step.clone());
+                npc.task.blocked(&npc.pos, state);
+            }
+        }
+    }
+}
+
+/// Tells the player that the npc gives up on reaching `goal`.
+pub struct UnreachableAction {
+    goal: MapPoint,
+}
+
+impl UnreachableAction {
+    pub fn new(goal: MapPoint) -> Self {
+        Self { goal }
+    }
+}
+
+impl Action for UnreachableAction {
+    fn execute(&self, npc: &mut Npc, state: &mut State) {
+        let name = state.get_base_npc(npc).name.clone();
+
+        state.post_message(
+            MessageCategory::Movement,
+            Severity::Warning,
+            format!(
+                "{name} cannot reach target at {}, {}",
+                self.goal.x, self.goal.y
+            ),
+        );
     }
 }
 
+/// Stands still, e.g. while waiting for a path.
+pub struct WaitAction {}
+
+impl Action for WaitAction {
+    fn execute(&self, _npc: &mut Npc, _state: &mut State) {}
+}
+
 #[derive(Clone)]
 pub struct GotoTask {
     goal: MapPoint,
-    tile_config: Rc<TileConfig>,
-    map: Rc<Map>,
+    profile: MovementProfile,
+    path: Option<PathHandle>,
 
     steps: Vec<MapPoint>,
     step_index: usize,
+    /// The index of the step handed out last, to retry it when it is blocked.
+    pending_step: Option<usize>,
+    blocked_ticks: u32,
 }
 
 impl GotoTask {
-    pub fn new(goal: MapPoint, map: Rc<Map>, tile_config: Rc<TileConfig>) -> Self {
+    pub fn new(goal: MapPoint) -> Self {
         Self {
             goal,
-            tile_config,
-            map,
+            profile: MovementProfile::default(),
+            path: None,
             steps: Vec::new(),
             step_index: 0,
+            pending_step: None,
+            blocked_ticks: 0,
         }
     }
 }
 
 impl Task for GotoTask {
-    fn assign(mut self: Box<Self>, npc: &mut Npc) {
-        self.steps = get_shortest_path(&npc.pos.clone(), &self.goal, &self.map, &self.tile_config);
+    fn assign(mut self: Box<Self>, npc: &mut Npc, state: &mut State) {
+        self.profile = state
+            .get_base_npc(npc)
+            .movement_profile
+            .clone();
+
+        self.path = Some(state.path_queue.request(
+            npc.pos.clone(),
+            self.goal.clone(),
+            self.profile.clone(),
+            HashSet::new(),
+        ));
 
         npc.task = self;
     }
@@ -55,19 +142,81 @@ impl Task for GotoTask {
     fn get_priority(&self) -> i32 {
         1
     }
+
+    fn blocked(&mut self, pos: &MapPoint, state: &mut State) {
+        let Some(blocked_step) = self.pending_step.take() else {
+            return;
+        };
+
+        self.step_index = blocked_step;
+        self.blocked_ticks += 1;
+
+        if self.blocked_ticks < MAX_BLOCKED_TICKS {
+            return;
+        }
+
+        self.blocked_ticks = 0;
+
+        let avoid = HashSet::from([self.steps[blocked_step].clone()]);
+        self.path = Some(state.path_queue.request(
+            pos.clone(),
+            self.goal.clone(),
+            self.profile.clone(),
+            avoid,
+        ));
+    }
 }
 
 impl Iterator for GotoTask {
     type Item = Box<dyn Action>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if let Some(path) = &self.path {
+            if !path.is_ready() {
+                return Some(Box::new(WaitAction {}));
+            }
+
+            // gives up if there is no way, e.g. when the goal is occupied by another npc
+            let result = path.take();
+            self.step_index = 0;
+            self.pending_step = None;
+            self.path = None;
+
+            match result {
+                Some(Ok(steps)) => self.steps = steps,
+                _ => {
+                    self.steps = Vec::new();
+                    return Some(Box::new(UnreachableAction {
+                        goal: self.goal.clone(),
+                    }));
+                }
+            }
+        }
+
         if self.step_index >= self.steps.len() {
             return None;
         }
 
         let next_step = self.steps[self.step_index].clone();
+        self.pending_step = Some(self.step_index);
         self.step_index += 1;
 
         Some(Box::new(GotoAction { next_step }))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_goto_blocked_before_first_step() {
+        let mut state = State::new();
+        let mut task = GotoTask::new(MapPoint::new(12, 12));
+
+        task.blocked(&MapPoint::new(10, 10), &mut state);
+
+        assert_eq!(task.step_index, 0);
+        assert_eq!(task.blocked_ticks, 0);
+    }
+}
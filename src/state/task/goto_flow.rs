@@ -0,0 +1,214 @@
+use std::rc::Rc;
+
+use super::goto::GotoAction;
+use super::goto::UnreachableAction;
+use super::Action;
+use super::Task;
+use super::MAX_BLOCKED_TICKS;
+use crate::common::MapPoint;
+use crate::movement_profile::MovementProfile;
+use crate::state::flow_field::FlowField;
+use crate::state::npc::Npc;
+use crate::state::path::get_traversable_neighbors;
+use crate::state::State;
+
+/// Walks to a goal by following a shared flow field, used for group orders.
+#[derive(Clone)]
+pub struct GotoFlowTask {
+    goal: MapPoint,
+    flow_field: Option<Rc<FlowField>>,
+    /// The map version the flow field was computed for.
+    map_version: u64,
+    profile: MovementProfile,
+
+    pos: MapPoint,
+    detour: Option<MapPoint>,
+    /// The step handed out last, cleared when it is blocked.
+    pending_step: Option<MapPoint>,
+    blocked_ticks: u32,
+    unreachable: bool,
+}
+
+impl GotoFlowTask {
+    pub fn new(goal: MapPoint) -> Self {
+        Self {
+            goal,
+            flow_field: None,
+            map_version: 0,
+            profile: MovementProfile::default(),
+            pos: MapPoint::new(0, 0),
+            detour: None,
+            pending_step: None,
+            blocked_ticks: 0,
+            unreachable: false,
+        }
+    }
+}
+
+impl Task for GotoFlowTask {
+    fn assign(mut self: Box<Self>, npc: &mut Npc, state: &mut State) {
+        self.profile = state
+            .get_base_npc(npc)
+            .movement_profile
+            .clone();
+
+        self.flow_field = Some(state.get_flow_field(&self.goal, &self.profile));
+        self.map_version = state.map_version;
+        self.pos = npc.pos.clone();
+
+        npc.task = self;
+    }
+
+    fn get_name(&self) -> String {
+        String::from("GotoFlow")
+    }
+
+    fn get_priority(&self) -> i32 {
+        1
+    }
+
+    fn blocked(&mut self, pos: &MapPoint, state: &mut State) {
+        let Some(blocked_step) = self.pending_step.take() else {
+            return;
+        };
+
+        self.pos = pos.clone();
+
+        // follows the steps of the changed map, the old ones may lead into solid tiles
+        if self.map_version != state.map_version {
+            self.flow_field = Some(state.get_flow_field(&self.goal, &self.profile));
+            self.map_version = state.map_version;
+            self.detour = None;
+            self.blocked_ticks = 0;
+            return;
+        }
+
+        self.blocked_ticks += 1;
+
+        if self.blocked_ticks < MAX_BLOCKED_TICKS {
+            return;
+        }
+
+        self.blocked_ticks = 0;
+
+        let flow_field = match &self.flow_field {
+            Some(f) => f,
+            None => return,
+        };
+
+        // gives up if the goal itself is taken, there is no other way onto it
+        if blocked_step == self.goal {
+            self.unreachable = true;
+            return;
+        }
+
+        // steps onto any free neighbor which is still closer to the goal
+        let cost = flow_field.get_cost(pos);
+        self.detour = get_traversable_neighbors(pos, &state.map, &state.tile_config, &self.profile)
+            .into_iter()
+            .filter(|t| !state.occupancy.is_occupied(&t.pos))
+            .filter_map(|t| {
+                flow_field
+                    .get_cost(&t.pos)
+                    .map(|c| (c, t.pos))
+            })
+            .filter(|(c, _)| Some(*c) < cost)
+            .min_by_key(|(c, _)| *c)
+            .map(|(_, p)| p);
+
+        // gives up if every tile closer to the goal is taken
+        self.unreachable = self.detour.is_none();
+    }
+}
+
+impl Iterator for GotoFlowTask {
+    type Item = Box<dyn Action>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let flow_field = self.flow_field.as_ref()?;
+
+        // the last step was not blocked, so the npc is moving again
+        if self.pending_step.is_some() {
+            self.blocked_ticks = 0;
+        }
+
+        let next_step = match self.detour.take() {
+            Some(detour) => Some(detour),
+            None if self.unreachable => None,
+            None => flow_field.get_next_step(&self.pos),
+        };
+
+        let Some(next_step) = next_step else {
+            if self.pos == self.goal {
+                return None;
+            }
+
+            // gives up if there is no way, e.g. when the last free tiles are taken
+            self.flow_field = None;
+            return Some(Box::new(UnreachableAction::new(self.goal.clone())));
+        };
+
+        self.pos = next_step.clone();
+        self.pending_step = Some(next_step.clone());
+
+        Some(Box::new(GotoAction::new(next_step)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::message_log::Message;
+    use crate::state::occupancy::Occupancy;
+
+    const DIRT_WALL: [char; 3] = ['[', ':', ']'];
+
+    #[test]
+    fn test_rally_to_single_tile_finishes() {
+        let mut state = State::new();
+        let goal = MapPoint::new(11, 11);
+
+        state.order_rally(goal.clone());
+
+        for _ in 0..30 {
+            state.update_npcs();
+        }
+
+        assert!(state.cursor_tasks.is_empty());
+        for npc in &state.npcs {
+            assert_ne!(npc.task.get_name(), "GotoFlow");
+        }
+        assert!(state.npcs.iter().any(|n| n.pos == goal));
+
+        let messages: Vec<&Message> = state
+            .message_log
+            .get_page(10, 0)
+            .collect();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].text, "Follower cannot reach target at 11, 11");
+    }
+
+    #[test]
+    fn test_rally_follows_changed_map() {
+        let mut state = State::new();
+        let goal = MapPoint::new(12, 14);
+
+        state.npcs.truncate(1);
+        state.npcs[0].pos = MapPoint::new(12, 10);
+        state.occupancy = Occupancy::new(&state.npcs);
+
+        state.order_rally(goal.clone());
+        state.update_npcs();
+        assert_eq!(state.npcs[0].pos, MapPoint::new(12, 11));
+
+        state.set_tile(&MapPoint::new(12, 12), DIRT_WALL);
+
+        for _ in 0..10 {
+            state.update_npcs();
+            assert!(state.is_tile_traversable(&state.npcs[0].pos, &MovementProfile::default()));
+        }
+
+        assert_eq!(state.npcs[0].pos, goal);
+        assert_ne!(state.npcs[0].task.get_name(), "GotoFlow");
+    }
+}
@@ -13,7 +13,7 @@ impl Action for IdleCursorAction {
 }
 
 impl Task for IdleCursorTask {
-    fn assign(self: Box<Self>, npc: &mut Npc) {
+    fn assign(self: Box<Self>, npc: &mut Npc, _state: &mut State) {
         npc.task = self;
     }
 
@@ -0,0 +1,126 @@
+use super::map::Map;
+use crate::common::MapPoint;
+use crate::tile_config::TileConfig;
+use crate::tile_config::TileState;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Visibility {
+    Unexplored,
+    /// Seen before, but currently out of sight of every friendly npc.
+    Remembered,
+    Visible,
+}
+
+/// The points within `radius` of `origin` not hidden behind a solid block.
+/// The solid blocks bordering the view are visible themselves.
+pub fn get_field_of_view(
+    origin: &MapPoint,
+    radius: i32,
+    map: &Map,
+    tile_config: &TileConfig,
+) -> Vec<MapPoint> {
+    let is_opaque = |p: &MapPoint| {
+        map.get_tile(p)
+            .map(|t| tile_config.get(t.tile_id).block_state == TileState::Solid)
+            .unwrap_or(true)
+    };
+
+    let mut field_of_view = Vec::new();
+
+    for y in origin.y - radius..=origin.y + radius {
+        for x in origin.x - radius..=origin.x + radius {
+            let target = MapPoint::new(x, y);
+            let offset = &target - origin;
+
+            if offset.x * offset.x + offset.y * offset.y > radius * radius
+                || map.get_tile(&target).is_none()
+            {
+                continue;
+            }
+
+            let line = get_line(origin, &target);
+            let mut between = line
+                .iter()
+                .skip(1)
+                .take(line.len().saturating_sub(2));
+
+            if between.all(|p| !is_opaque(p)) {
+                field_of_view.push(target);
+            }
+        }
+    }
+
+    field_of_view
+}
+
+/// The points of the Bresenham line from `from` to `to`, both included.
+fn get_line(from: &MapPoint, to: &MapPoint) -> Vec<MapPoint> {
+    let dx = (to.x - from.x).abs();
+    let dy = -(to.y - from.y).abs();
+    let sx = if from.x < to.x { 1 } else { -1 };
+    let sy = if from.y < to.y { 1 } else { -1 };
+
+    let mut line = Vec::new();
+    let mut point = from.clone();
+    let mut error = dx + dy;
+
+    loop {
+        line.push(point.clone());
+
+        if point == *to {
+            return line;
+        }
+
+        let double_error = 2 * error;
+
+        if double_error >= dy {
+            error += dy;
+            point.x += sx;
+        }
+
+        if double_error <= dx {
+            error += dx;
+            point.y += sy;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::State;
+
+    #[test]
+    fn test_field_of_view_stops_at_walls() {
+        let state = State::new();
+        let tile_ids = "[_][_][:][_]\n[_][_][:][_]\n[_][_][_][_]\n[_][_][_][_]\n";
+        let map = Map::from_tile_ids(tile_ids, &state.tile_config);
+
+        let field_of_view = get_field_of_view(&MapPoint::new(0, 0), 5, &map, &state.tile_config);
+
+        assert!(field_of_view.contains(&MapPoint::new(2, 0)));
+        assert!(field_of_view.contains(&MapPoint::new(3, 3)));
+        assert!(!field_of_view.contains(&MapPoint::new(3, 0)));
+    }
+
+    #[test]
+    fn test_visibility_is_remembered() {
+        let mut state = State::new();
+        let point = state.npcs[0].pos.clone();
+
+        assert_eq!(state.map.get_visibility(&point), Visibility::Visible);
+        assert_eq!(
+            state
+                .map
+                .get_visibility(&MapPoint::new(3, 3)),
+            Visibility::Unexplored
+        );
+
+        for npc in &mut state.npcs {
+            npc.pos = MapPoint::new(20, 8);
+        }
+        state.update_visibility();
+
+        assert_eq!(state.map.get_visibility(&point), Visibility::Remembered);
+    }
+}
@@ -0,0 +1,94 @@
+/// Text attributes of a pixel.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Style {
+    pub bold: bool,
+    pub dim: bool,
+    pub underline: bool,
+    pub blink: bool,
+}
+
+impl Style {
+    pub const fn bold() -> Self {
+        Self {
+            bold: true,
+            dim: false,
+            underline: false,
+            blink: false,
+        }
+    }
+
+    /// The escape sequence changing the attributes of a terminal from `self` to `to`.
+    pub fn transition(&self, to: &Style) -> String {
+        let mut codes = Vec::new();
+
+        // bold and dim can only be turned off together
+        let intensity_off = (self.bold && !to.bold) || (self.dim && !to.dim);
+        if intensity_off {
+            codes.push(22);
+        }
+        if to.bold && (!self.bold || intensity_off) {
+            codes.push(1);
+        }
+        if to.dim && (!self.dim || intensity_off) {
+            codes.push(2);
+        }
+
+        if self.underline != to.underline {
+            codes.push(if to.underline { 4 } else { 24 });
+        }
+
+        if self.blink != to.blink {
+            codes.push(if to.blink { 5 } else { 25 });
+        }
+
+        if codes.is_empty() {
+            return String::new();
+        }
+
+        let codes: Vec<String> = codes
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+
+        format!("\x1b[{}m", codes.join(";"))
+    }
+}
+
+impl From<&toml::Value> for Style {
+    fn from(value: &toml::Value) -> Self {
+        let mut style = Style::default();
+
+        for attribute in value.as_array().unwrap() {
+            match attribute.as_str().unwrap() {
+                "bold" => style.bold = true,
+                "dim" => style.dim = true,
+                "underline" => style.underline = true,
+                "blink" => style.blink = true,
+                a => panic!("Style attribute '{a}' unkown."),
+            }
+        }
+
+        style
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_style_transition() {
+        let plain = Style::default();
+        let bold = Style::bold();
+        let dim_underline = Style {
+            dim: true,
+            underline: true,
+            ..Style::default()
+        };
+
+        assert_eq!(plain.transition(&plain), "");
+        assert_eq!(plain.transition(&bold), "\x1b[1m");
+        assert_eq!(bold.transition(&plain), "\x1b[22m");
+        assert_eq!(bold.transition(&dim_underline), "\x1b[22;2;4m");
+    }
+}
@@ -0,0 +1,65 @@
+use std::io::Stdout;
+use std::io::Write;
+
+use termion::raw::RawTerminal;
+
+use crate::color::ColorMode;
+use crate::common::ScreenPoint;
+
+/// Where a `Screen` writes its output to.
+pub trait Terminal: Write {
+    fn size(&self) -> ScreenPoint;
+
+    fn color_mode(&self) -> ColorMode;
+}
+
+impl Terminal for RawTerminal<Stdout> {
+    fn size(&self) -> ScreenPoint {
+        let (cols, rows) = termion::terminal_size().unwrap();
+
+        ScreenPoint::new(cols as i32, rows as i32)
+    }
+
+    fn color_mode(&self) -> ColorMode {
+        ColorMode::detect()
+    }
+}
+
+/// A terminal of fixed size collecting everything written to it.
+#[cfg(test)]
+pub struct Headless {
+    pub output: std::rc::Rc<std::cell::RefCell<Vec<u8>>>,
+    pub size: ScreenPoint,
+}
+
+#[cfg(test)]
+impl Headless {
+    pub fn new(size: ScreenPoint) -> Self {
+        Self {
+            output: Default::default(),
+            size,
+        }
+    }
+}
+
+#[cfg(test)]
+impl Write for Headless {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.output.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl Terminal for Headless {
+    fn size(&self) -> ScreenPoint {
+        self.size.clone()
+    }
+
+    fn color_mode(&self) -> ColorMode {
+        ColorMode::Indexed256
+    }
+}
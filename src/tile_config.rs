@@ -3,11 +3,17 @@ use std::fs::read_to_string;
 use std::path::Path;
 
 use crate::color::Color;
+use crate::color::ColorValue;
+use crate::common::MapPoint;
+use crate::movement_profile::MovementProfile;
 use crate::screen::Animation;
+use crate::screen::Sprite;
 
 pub type TileId = [char; 3];
 
-#[derive(Clone, Debug, PartialEq)]
+pub const DEFAULT_MOVE_COST: u32 = 10;
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum TileState {
     Solid,
     Liquid,
@@ -31,21 +37,95 @@ impl From<String> for TileState {
     }
 }
 
+/// Replaces the look of a tile whose neighbors at the `exposed` offsets are all
+/// open, i.e. have no solid block, e.g. the face of a wall next to a floor.
+#[derive(Clone, Debug)]
+pub struct AutotileRule {
+    pub animations: Vec<Animation>,
+    pub exposed: Vec<MapPoint>,
+    pub glyph: Sprite,
+}
+
+impl AutotileRule {
+    fn from_toml(rule: &toml::Value, color: Color, glyph: &Sprite) -> Self {
+        let animations = rule["animations"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(Animation::from)
+            .map(Animation::with_color(color))
+            .collect();
+        let exposed = rule["exposed"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|d| get_direction(d.as_str().unwrap()))
+            .collect();
+        let glyph = rule
+            .get("glyph")
+            .and_then(|g| g.as_str())
+            .map(|g| Sprite::from_color_text(g, color))
+            .unwrap_or(glyph.clone());
+
+        Self {
+            animations,
+            exposed,
+            glyph,
+        }
+    }
+}
+
+fn get_direction(key: &str) -> MapPoint {
+    match key {
+        "left" => MapPoint::new(-1, 0),
+        "right" => MapPoint::new(1, 0),
+        "up" => MapPoint::new(0, -1),
+        "down" => MapPoint::new(0, 1),
+        "up_left" => MapPoint::new(-1, -1),
+        "up_right" => MapPoint::new(1, -1),
+        "down_left" => MapPoint::new(-1, 1),
+        "down_right" => MapPoint::new(1, 1),
+        _ => panic!("Direction '{key}' unkown."),
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct BaseTile {
     pub block_state: TileState,
     pub color: Color,
     pub floor_state: TileState,
+    /// The tile drawn as a single cell in compact zoom.
+    pub glyph: Sprite,
     pub id: TileId,
     pub key: String,
+    /// How many tiles far the tile lights up its surroundings.
+    pub light: i32,
     pub minable: bool,
+    pub move_cost: u32,
     pub name: String,
     pub animations: Vec<Animation>,
+    /// Alternative looks depending on the neighbors, the first matching rule wins.
+    pub autotile: Vec<AutotileRule>,
 }
 
 impl BaseTile {
-    pub fn is_traversable(&self) -> bool {
-        self.block_state == TileState::Gas && self.floor_state == TileState::Solid
+    /// The animations of the autotile rule `variant`, or the default ones.
+    pub fn get_animations(&self, variant: Option<usize>) -> &[Animation] {
+        match variant {
+            Some(v) => &self.autotile[v].animations,
+            None => &self.animations,
+        }
+    }
+
+    pub fn get_glyph(&self, variant: Option<usize>) -> &Sprite {
+        match variant {
+            Some(v) => &self.autotile[v].glyph,
+            None => &self.glyph,
+        }
+    }
+
+    pub fn is_traversable(&self, profile: &MovementProfile) -> bool {
+        profile.traversability.allows(self)
     }
 }
 
@@ -59,6 +139,10 @@ impl TileConfig {
         self.tiles.get(&tile_id).unwrap()
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = &BaseTile> {
+        self.tiles.values()
+    }
+
     pub fn from_file<P: AsRef<Path>>(path: P) -> Self {
         let mut tiles = HashMap::new();
 
@@ -67,15 +151,30 @@ impl TileConfig {
         let tile_confg_table = tile_config.as_table().unwrap();
 
         for (key, t) in tile_confg_table {
-            let bg_color = t["bg_color"].as_integer().unwrap() as u8;
-            let fg_color = t["fg_color"].as_integer().unwrap() as u8;
+            let parse_color = |color_key: &str| {
+                ColorValue::parse(&t[color_key]).unwrap_or_else(|e| {
+                    panic!("Color '{color_key}' of tile '{key}' is invalid: {e:?}.")
+                })
+            };
+            let bg_color = parse_color("bg_color");
+            let fg_color = parse_color("fg_color");
 
             let key = key.to_string();
-            let color = Color::new(bg_color, fg_color);
+            let color = Color { bg_color, fg_color };
             let minable = t
                 .get("minable")
                 .and_then(|v| v.as_bool())
                 .unwrap_or(false);
+            let light = t
+                .get("light")
+                .and_then(|v| v.as_integer())
+                .map(|l| l as i32)
+                .unwrap_or(0);
+            let move_cost = t
+                .get("move_cost")
+                .and_then(|v| v.as_integer())
+                .map(|c| c as u32)
+                .unwrap_or(DEFAULT_MOVE_COST);
             let name = t["name"].as_str().unwrap().to_string();
             let floor_state = TileState::from(t["floor_state"].as_str().unwrap());
             let block_state = TileState::from(t["block_state"].as_str().unwrap());
@@ -85,6 +184,12 @@ impl TileConfig {
                 id_chars.next().unwrap(),
                 id_chars.next().unwrap(),
             ];
+            let glyph = match t.get("glyph").and_then(|g| g.as_str()) {
+                Some(glyph) if glyph.chars().count() == 1 => glyph.to_string(),
+                Some(glyph) => panic!("Glyph '{glyph}' of tile '{key}' is not a single character."),
+                None => id[1].to_string(),
+            };
+            let glyph = Sprite::from_color_text(&glyph, color);
             let animations = t["animations"]
                 .as_array()
                 .unwrap()
@@ -92,6 +197,16 @@ impl TileConfig {
                 .map(Animation::from)
                 .map(Animation::with_color(color))
                 .collect();
+            let autotile = t
+                .get("autotile")
+                .and_then(|a| a.as_array())
+                .map(|rules| {
+                    rules
+                        .iter()
+                        .map(|r| AutotileRule::from_toml(r, color, &glyph))
+                        .collect()
+                })
+                .unwrap_or_default();
 
             tiles.insert(
                 id,
@@ -99,11 +214,15 @@ impl TileConfig {
                     block_state,
                     color,
                     floor_state,
+                    glyph,
                     id,
                     key,
+                    light,
                     minable,
+                    move_cost,
                     name,
                     animations,
+                    autotile,
                 },
             );
         }